@@ -0,0 +1,112 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::reserve::MAX_BANK;
+
+/// A set of banks given as a comma separated list of bank numbers
+/// and ranges, e.g. `254,255` or `1-4,8`
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "BankSetValue")]
+pub struct BankSet {
+    pub ranges: Vec<(u32, u32)>,
+}
+
+impl BankSet {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, bank: u32) -> bool {
+        self.ranges.iter().any(|&(from, to)| (from..=to).contains(&bank))
+    }
+
+    /// Every bank in the set in ascending order
+    pub fn banks(&self) -> Vec<u32> {
+        let mut banks: Vec<u32> = self.ranges.iter().flat_map(|&(from, to)| from..=to).collect();
+        banks.sort_unstable();
+        banks.dedup();
+        banks
+    }
+}
+
+impl FromStr for BankSet {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let ranges = spec
+            .split(',')
+            .filter(|range| !range.trim().is_empty())
+            .map(|range| parse_range(range.trim(), 0).map_err(|err| format!("Invalid bank list \"{}\": {}", spec, err)))
+            .collect::<Result<Vec<(u32, u32)>, String>>()?;
+        Ok(BankSet { ranges })
+    }
+}
+
+impl fmt::Display for BankSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranges: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|&(from, to)| if from == to { from.to_string() } else { format!("{}-{}", from, to) })
+            .collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+/// Parse a bank number or a range of banks such as `1-4`, shared by bank
+/// lists and reserve rules so both accept the same syntax. Banks must be
+/// between `min_bank` and `MAX_BANK`.
+pub(crate) fn parse_range(range: &str, min_bank: u32) -> Result<(u32, u32), String> {
+    let (from, to) = match range.find('-') {
+        Some(index) => (
+            parse_bank(&range[..index], min_bank)?,
+            parse_bank(&range[index + 1..], min_bank)?,
+        ),
+        None => {
+            let bank = parse_bank(range, min_bank)?;
+            (bank, bank)
+        }
+    };
+    if from > to {
+        return Err(format!("bank range {} is reversed", range));
+    }
+    Ok((from, to))
+}
+
+fn parse_bank(bank: &str, min_bank: u32) -> Result<u32, String> {
+    let bank = bank
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("\"{}\" is not a bank number", bank))?;
+    if !(min_bank..=MAX_BANK).contains(&bank) {
+        return Err(format!("bank {} is out of range ({}-{})", bank, min_bank, MAX_BANK));
+    }
+    Ok(bank)
+}
+
+/// Bank sets in a config file can be a single bank, a list of banks or a list string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BankSetValue {
+    Bank(u32),
+    Banks(Vec<u32>),
+    Spec(String),
+}
+
+impl TryFrom<BankSetValue> for BankSet {
+    type Error = String;
+
+    fn try_from(value: BankSetValue) -> Result<Self, Self::Error> {
+        match value {
+            BankSetValue::Bank(bank) => BankSet::from_str(&bank.to_string()),
+            BankSetValue::Banks(banks) => banks
+                .iter()
+                .map(|bank| parse_bank(&bank.to_string(), 0).map(|bank| (bank, bank)))
+                .collect::<Result<Vec<(u32, u32)>, String>>()
+                .map(|ranges| BankSet { ranges }),
+            BankSetValue::Spec(spec) => BankSet::from_str(&spec),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use glob::Pattern;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::banks::BankSet;
+use crate::emit::AsmSyntax;
+use crate::options::Strategy;
+use crate::Mapper;
+
+/// Config file read from the working directory when `--config` isn't given
+pub const DEFAULT_CONFIG_FILENAME: &str = "gbspack.toml";
+
+/// Packing options loaded from a `gbspack.toml` project config.
+/// Every value is optional so that command line flags can override it.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bank: Option<u32>,
+    /// Banks to repack, a single bank, a list of banks or a list string such as "250-255"
+    pub filter: Option<BankSet>,
+    pub mapper: Option<Mapper>,
+    /// Banks autobanked areas may be packed into
+    pub allow_banks: Option<BankSet>,
+    /// Banks autobanked areas must never be packed into
+    pub deny_banks: Option<BankSet>,
+    pub strategy: Option<Strategy>,
+    /// Fill banks from the top bank downwards
+    pub descending: bool,
+    /// Highest bank to pack into
+    pub top_bank: Option<u32>,
+    pub additional: Option<u32>,
+    /// Reserve specs in the same `bank:hexsize` format as `--reserve`
+    pub reserve: Vec<String>,
+    /// Objects to place in a fixed bank, keyed by path or file name
+    pub pins: BTreeMap<String, u32>,
+    /// Lists of objects which must be packed into the same bank
+    pub groups: Vec<Vec<String>>,
+    /// Data-only objects whose areas may span consecutive banks, by path, file name or glob pattern
+    pub spanning: Vec<String>,
+    /// Lists of objects packed into consecutive banks in order
+    pub sequences: Vec<Vec<String>>,
+    /// Objects the engine can read without switching banks, which may be moved into ROM0
+    pub unbanked: Vec<String>,
+    /// Bytes free in ROM0, e.g. "2K", read from `link_map` if not given
+    pub rom0_free: Option<String>,
+    /// `.map` or `.noi` file from a previous link used to seed bank usage
+    pub link_map: Option<String>,
+    pub input: Option<String>,
+    pub inputs: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub output: Option<String>,
+    pub ext: Option<String>,
+    /// C header to write with the bank of each packed symbol
+    pub header: Option<String>,
+    /// Assembly include to write with the bank of each packed symbol
+    pub asm_include: Option<String>,
+    pub asm_syntax: Option<AsmSyntax>,
+    /// JSON record of the packing result to write
+    pub lock: Option<String>,
+    /// Named sets of objects packed into their own range of banks
+    pub pools: Vec<PoolConfig>,
+    pub report: ReportConfig,
+    pub linker: LinkerConfig,
+    pub batteryless: BatterylessConfig,
+}
+
+impl Config {
+    /// Resolve the relative file and directory paths in the config against
+    /// `base`, the directory holding the config file, so a project config
+    /// works from any working directory. Object names and patterns used to
+    /// match inputs are left as they are.
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut String| {
+            // An `@file` input keeps its prefix with the file resolved after it
+            let (prefix, file) = match path.strip_prefix('@') {
+                Some(file) => ("@", file),
+                None => ("", path.as_str()),
+            };
+            if !file.is_empty() {
+                *path = format!("{}{}", prefix, base.join(file).to_string_lossy());
+            }
+        };
+        self.input.iter_mut().for_each(resolve);
+        self.inputs.iter_mut().for_each(resolve);
+        self.output.iter_mut().for_each(resolve);
+        self.link_map.iter_mut().for_each(resolve);
+        self.header.iter_mut().for_each(resolve);
+        self.asm_include.iter_mut().for_each(resolve);
+        self.lock.iter_mut().for_each(resolve);
+        self.report.file.iter_mut().for_each(resolve);
+        self.linker.file.iter_mut().for_each(resolve);
+        for pool in self.pools.iter_mut() {
+            pool.inputs.iter_mut().for_each(resolve);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolConfig {
+    pub name: String,
+    pub banks: BankSet,
+    /// Objects in the pool, by path, file name or glob pattern
+    pub objects: Vec<String>,
+    /// Extra input files, directories or glob patterns packed in the pool
+    pub inputs: Vec<String>,
+    /// Pool to spill into when this pool is full
+    pub fallback: Option<String>,
+}
+
+impl FromStr for PoolConfig {
+    type Err = String;
+
+    /// Parse a pool given on the command line in the format
+    /// `name:banks:objects[:fallback]` e.g. `music:8-15:music_*.o`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let split = spec.split(':').collect::<Vec<&str>>();
+        if split.len() < 3 || split.len() > 4 || split[0].trim().is_empty() {
+            return Err(format!(
+                "Invalid pool \"{}\", expected format name:banks:objects[:fallback] e.g. music:8-15:music_*.o",
+                spec
+            ));
+        }
+        Ok(PoolConfig {
+            name: split[0].trim().to_string(),
+            banks: split[1].parse()?,
+            objects: split[2]
+                .split(',')
+                .map(|entry| entry.trim().to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect(),
+            inputs: vec![],
+            fallback: split.get(3).map(|fallback| fallback.trim().to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatterylessConfig {
+    /// Bytes of save data to keep free at the end of the cart, e.g. "32K"
+    pub size: Option<String>,
+    /// Flash sector size the save block is aligned to, e.g. "64K"
+    pub sector: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkerConfig {
+    /// Options file to write with the cartridge flags for the link step
+    pub file: Option<String>,
+    pub ram_banks: Option<u32>,
+    pub battery: bool,
+    /// Fix the base address of each packed bank in the options file
+    pub base_addresses: bool,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportConfig {
+    pub file: Option<String>,
+    pub head: Option<String>,
+    pub print_max: bool,
+    pub print_cart: bool,
+}
+
+/// Parse the contents of a `gbspack.toml` file
+pub fn parse_config(contents: &str) -> std::io::Result<Config> {
+    toml::from_str(contents).map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))
+}
+
+/// Load config from the given file, or from `gbspack.toml` in the working
+/// directory if it exists. Returns the default config when neither is found.
+/// Relative paths in the config are taken from the config file's directory.
+pub fn load_config(filename: Option<&str>) -> std::io::Result<Config> {
+    let filename = match filename {
+        Some(filename) => filename,
+        None if Path::new(DEFAULT_CONFIG_FILENAME).is_file() => DEFAULT_CONFIG_FILENAME,
+        None => return Ok(Config::default()),
+    };
+    let contents = fs::read_to_string(filename)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to open file \"{}\": {}", filename, err)))?;
+    let mut config = parse_config(&contents)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to parse config \"{}\": {}", filename, err)))?;
+    if let Some(base) = Path::new(filename).parent() {
+        config.resolve_paths(base);
+    }
+    Ok(config)
+}
+
+/// Check if a config entry refers to an object, either by its full path
+/// or by its file name
+pub fn matches_object(entry: &str, filename: &str) -> bool {
+    entry == filename || Path::new(filename).file_name() == Some(OsStr::new(entry))
+}
+
+/// Check if a config entry refers to an object as with `matches_object`,
+/// or if it is a glob pattern matching the object's path or file name
+pub fn matches_pattern(entry: &str, filename: &str) -> bool {
+    if matches_object(entry, filename) {
+        return true;
+    }
+    let name = Path::new(filename).file_name().and_then(OsStr::to_str).unwrap_or(filename);
+    Pattern::new(entry).map_or(false, |pattern| pattern.matches(name) || pattern.matches(filename))
+}
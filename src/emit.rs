@@ -0,0 +1,145 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::save::SaveBlock;
+use crate::{ObjectData, ObjectPatch};
+
+/// Assembler syntax used for generated include files
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AsmSyntax {
+    Asxxxx,
+    Rgbds,
+}
+
+impl FromStr for AsmSyntax {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "asxxxx" => Ok(AsmSyntax::Asxxxx),
+            "rgbds" => Ok(AsmSyntax::Rgbds),
+            _ => Err(format!("Unknown assembler syntax \"{}\", expected asxxxx or rgbds", name)),
+        }
+    }
+}
+
+/// A `___bank_` symbol and the bank it was packed into
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BankSymbol {
+    /// Symbol name without the `___bank_` prefix
+    pub name: String,
+    pub bank: u32,
+}
+
+/// Find the `___bank_` symbols defined by the packed objects whose bank was
+/// changed, along with their new bank numbers. Each symbol is matched to the
+/// replacement for its bank in the same way as `replace_all_banks` and compared
+/// with its bank in `inputs`, the objects as read before any pins were applied,
+/// so pinned objects are included. Symbols defined by more than one object or
+/// which can't be used in a C identifier are reported as errors.
+pub fn bank_symbols(packed: &[ObjectPatch], inputs: &[ObjectData]) -> Result<Vec<BankSymbol>, String> {
+    let re = Regex::new(r"(?m)^S ___bank_(\S+) Def([0-9A-Fa-f]+)").unwrap();
+    let mut defined: HashMap<String, &str> = HashMap::new();
+    let mut symbols: Vec<BankSymbol> = vec![];
+    for patch in packed {
+        let original: HashMap<&str, u32> = inputs
+            .iter()
+            .filter(|input| input.filename == patch.filename)
+            .flat_map(|input| re.captures_iter(&input.contents))
+            .map(|caps| {
+                let name = caps.get(1).unwrap().as_str();
+                (name, u32::from_str_radix(&caps[2], 16).unwrap())
+            })
+            .collect();
+        for caps in re.captures_iter(&patch.contents) {
+            let name = &caps[1];
+            let value = u32::from_str_radix(&caps[2], 16).unwrap();
+            if let Some(filename) = defined.insert(name.to_string(), &patch.filename) {
+                return Err(format!(
+                    "Symbol ___bank_{} is defined in both {} and {}",
+                    name, filename, patch.filename
+                ));
+            }
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "Symbol ___bank_{} in {} can't be used as a C identifier",
+                    name, patch.filename
+                ));
+            }
+            let bank = patch
+                .replacements
+                .iter()
+                .find(|r| r.from == value)
+                .map_or(value, |replacement| replacement.to);
+            if original.get(name).copied().unwrap_or(value) != bank {
+                symbols.push(BankSymbol {
+                    name: name.to_string(),
+                    bank,
+                });
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// Generate a C header defining the bank of each symbol along with
+/// the max bank, cart size and any batteryless save banks
+pub fn to_c_header(symbols: &[BankSymbol], max_bank: u32, cart_size: u32, save: Option<&SaveBlock>) -> String {
+    let mut lines = vec![
+        "/* Generated by gbspack, do not edit */".to_string(),
+        "#ifndef GBSPACK_BANKS_H".to_string(),
+        "#define GBSPACK_BANKS_H".to_string(),
+        "".to_string(),
+        format!("#define GBSPACK_MAX_BANK {}", max_bank),
+        format!("#define GBSPACK_CART_SIZE {}", cart_size),
+    ];
+    if let Some(save) = save {
+        lines.push(format!("#define GBSPACK_SAVE_FIRST_BANK {}", save.first_bank));
+        lines.push(format!("#define GBSPACK_SAVE_LAST_BANK {}", save.last_bank));
+        lines.push(format!("#define GBSPACK_SAVE_BANKS {}", save.banks()));
+    }
+    lines.push("".to_string());
+    for symbol in symbols {
+        lines.push(format!("#define BANK_{} {}", symbol.name, symbol.bank));
+    }
+    lines.push("".to_string());
+    lines.push("#endif".to_string());
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+/// Generate an assembly include file exporting the bank of each symbol
+/// along with the max bank, cart size and any batteryless save banks as
+/// global constants
+pub fn to_asm_include(
+    symbols: &[BankSymbol],
+    syntax: AsmSyntax,
+    max_bank: u32,
+    cart_size: u32,
+    save: Option<&SaveBlock>,
+) -> String {
+    let constant: fn(&str, u32) -> String = match syntax {
+        AsmSyntax::Asxxxx => |name, value| format!("{} == {}", name, value),
+        AsmSyntax::Rgbds => |name, value| format!("DEF {} EQU {}", name, value),
+    };
+    let mut lines = vec![
+        "; Generated by gbspack, do not edit".to_string(),
+        "".to_string(),
+        constant("GBSPACK_MAX_BANK", max_bank),
+        constant("GBSPACK_CART_SIZE", cart_size),
+    ];
+    if let Some(save) = save {
+        lines.push(constant("GBSPACK_SAVE_FIRST_BANK", save.first_bank));
+        lines.push(constant("GBSPACK_SAVE_LAST_BANK", save.last_bank));
+        lines.push(constant("GBSPACK_SAVE_BANKS", save.banks()));
+    }
+    lines.push("".to_string());
+    for symbol in symbols {
+        lines.push(constant(&format!("BANK_{}", symbol.name), symbol.bank));
+    }
+    lines.push("".to_string());
+    lines.join("\n")
+}
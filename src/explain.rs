@@ -0,0 +1,62 @@
+/// Why a bank was or wasn't used when placing an area
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AttemptOutcome {
+    /// Bank is below the bank offset so was never considered
+    BelowOffset,
+    /// Bank is denied, not in the allowed banks or can't be addressed by the mapper
+    Denied,
+    /// Not enough free space in the bank
+    NoSpace,
+    /// The area would fit but space reserved in the bank rules it out
+    Reserved,
+    /// The area would fit but a better bank was chosen
+    Fits,
+    /// The area was placed in this existing bank
+    Placed,
+    /// No existing bank had room so a new bank was created for the area
+    NewBank,
+}
+
+/// A single bank the packer tried while placing an area
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BankAttempt {
+    pub bank: u32,
+    /// Bytes already used in the bank
+    pub used: u32,
+    /// Bytes reserved in the bank
+    pub reserved: u32,
+    /// Bytes needed, including any areas grouped with this one
+    pub required: u32,
+    /// Bytes which can be packed into the bank
+    pub capacity: u32,
+    pub outcome: AttemptOutcome,
+}
+
+impl BankAttempt {
+    /// Bytes free in the bank after reservations
+    pub fn free(&self) -> u32 {
+        self.capacity.saturating_sub(self.used + self.reserved)
+    }
+}
+
+/// Trace of how one area of an object was placed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AreaTrace {
+    /// Bank of the area in the input object file
+    pub from: u32,
+    pub size: u32,
+    /// Position of the area in the descending size order the packer
+    /// places areas in, starting at 1, or `None` for fixed areas
+    pub position: Option<usize>,
+    /// Number of areas or groups of areas the packer placed
+    pub count: usize,
+    pub attempts: Vec<BankAttempt>,
+    /// Bank the area ended up in
+    pub bank: u32,
+}
+
+impl AreaTrace {
+    pub fn is_fixed(&self) -> bool {
+        self.position.is_none()
+    }
+}
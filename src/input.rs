@@ -0,0 +1,278 @@
+use glob::Pattern;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Linker flags which take their value as the following argument
+const VALUE_FLAGS: [&str; 5] = ["-o", "-k", "-l", "-b", "-g"];
+
+/// Extensions of linker outputs which can appear as bare arguments
+/// in a linker command file but are never object files
+const OUTPUT_EXTENSIONS: [&str; 8] = ["ihx", "gb", "gbc", "pocket", "duck", "map", "noi", "sym"];
+
+/// Expand command line inputs, replacing any `@file` argument with
+/// the object files listed in that response file
+pub fn expand_input_args(args: &[String]) -> std::io::Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    let mut stack = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('@') {
+            Some(filename) => read_nested(filename, &mut stack, &mut inputs)?,
+            None => inputs.push(arg.to_string()),
+        }
+    }
+    Ok(inputs)
+}
+
+/// Load the object files listed in a plain list, lcc `@response` file
+/// or sdldgb `-f` linker command file
+pub fn read_input_file(filename: &str) -> std::io::Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    read_nested(filename, &mut Vec::new(), &mut inputs)?;
+    Ok(inputs)
+}
+
+fn read_nested(filename: &str, stack: &mut Vec<String>, inputs: &mut Vec<String>) -> std::io::Result<()> {
+    if stack.iter().any(|f| f == filename) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Input file \"{}\" includes itself", filename),
+        ));
+    }
+
+    let mut contents = String::new();
+    File::open(filename)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|err| Error::new(err.kind(), format!("Unable to open file \"{}\": {}", filename, err)))?;
+
+    stack.push(filename.to_string());
+    for arg in parse_input_list(&contents) {
+        match arg {
+            InputArg::Object(path) => inputs.push(path),
+            InputArg::Include(path) => read_nested(&path, stack, inputs)?,
+        }
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+/// Default filter used when expanding directories without `--include` patterns
+const DEFAULT_INCLUDE: &str = "*.o";
+
+/// Expand directory and glob pattern inputs into the object files they contain.
+/// Directories are searched recursively for files matching one of the `include`
+/// patterns (default `*.o`) and files matching an `exclude` pattern are dropped.
+/// Each expansion is sorted so the packed result doesn't depend on the order
+/// the filesystem returns entries, and files listed more than once are only
+/// packed once.
+pub fn resolve_inputs(inputs: &[String], include: &[String], exclude: &[String]) -> std::io::Result<Vec<String>> {
+    let include = compile_patterns(include)?;
+    let include = if include.is_empty() {
+        vec![Pattern::new(DEFAULT_INCLUDE).unwrap()]
+    } else {
+        include
+    };
+    let exclude = compile_patterns(exclude)?;
+
+    let mut resolved: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for input in inputs {
+        let path = Path::new(input);
+        // An existing file is taken as it is, even if its name looks like a pattern
+        let mut expanded = if path.is_file() {
+            vec![PathBuf::from(input)]
+        } else if path.is_dir() {
+            let mut files = Vec::new();
+            collect_dir(path, &mut files)?;
+            files
+                .into_iter()
+                .filter(|file| matches_any(&include, path, file) && !matches_any(&exclude, path, file))
+                .collect()
+        } else if is_glob(input) {
+            let paths = glob::glob(input).map_err(|err| {
+                Error::new(ErrorKind::InvalidInput, format!("Invalid pattern \"{}\": {}", input, err))
+            })?;
+            let files: Vec<PathBuf> = paths
+                .filter_map(|entry| entry.ok())
+                .filter(|file| file.is_file())
+                .collect();
+            if files.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("No files match pattern \"{}\"", input),
+                ));
+            }
+            files
+                .into_iter()
+                .filter(|file| !matches_any(&exclude, path, file))
+                .collect()
+        } else {
+            vec![PathBuf::from(input)]
+        };
+
+        expanded.sort();
+        for file in expanded {
+            let file = match file.to_str() {
+                Some(file) => file.to_owned(),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("File name \"{}\" is not valid UTF-8", file.display()),
+                    ))
+                }
+            };
+            if seen.insert(file.clone()) {
+                resolved.push(file);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+fn compile_patterns(patterns: &[String]) -> std::io::Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).map_err(|err| {
+                Error::new(ErrorKind::InvalidInput, format!("Invalid pattern \"{}\": {}", pattern, err))
+            })
+        })
+        .collect()
+}
+
+/// Check a file against filter patterns using its file name or
+/// its path relative to the input it was found in
+fn matches_any(patterns: &[Pattern], root: &Path, file: &Path) -> bool {
+    let relative = file.strip_prefix(root).unwrap_or(file);
+    let name = file.file_name().map(Path::new).unwrap_or(file);
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_path(name) || pattern.matches_path(relative))
+}
+
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to read directory \"{}\": {}", dir.display(), err)))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_dir(&path, files)?;
+        } else if !(file_type.is_symlink() && path.is_dir()) {
+            // Symlinked directories are skipped so links back up the tree can't loop
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(|c| matches!(c, '*' | '?' | '['))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InputArg {
+    Object(String),
+    Include(String),
+}
+
+/// Parse the contents of an input list into object files and nested includes.
+/// Flags and their values are skipped, `#` and `;` start comments and
+/// a `-e` argument ends a linker command file.
+pub fn parse_input_list(contents: &str) -> Vec<InputArg> {
+    let mut args = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        // A bare path per line, possibly containing spaces, as written by older builds
+        if !trimmed.is_empty()
+            && !trimmed.starts_with(|c| matches!(c, '-' | '@' | '#' | ';'))
+            && !trimmed.contains(|c| matches!(c, '"' | '\''))
+            && Path::new(trimmed).is_file()
+        {
+            args.push(trimmed.to_string());
+            continue;
+        }
+        args.extend(split_args(line));
+    }
+
+    let mut inputs = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-e" {
+            break;
+        } else if let Some(path) = arg.strip_prefix('@') {
+            inputs.push(InputArg::Include(path.to_string()));
+        } else if let Some(path) = arg.strip_prefix("-Wl-f").or_else(|| arg.strip_prefix("-f")) {
+            match path {
+                "" => {
+                    if let Some(path) = iter.next() {
+                        inputs.push(InputArg::Include(path));
+                    }
+                }
+                _ => inputs.push(InputArg::Include(path.to_string())),
+            }
+        } else if VALUE_FLAGS.contains(&arg.as_str()) {
+            iter.next();
+        } else if arg.starts_with('-') {
+            continue;
+        } else if !is_output_file(&arg) {
+            inputs.push(InputArg::Object(arg));
+        }
+    }
+    inputs
+}
+
+fn is_output_file(arg: &str) -> bool {
+    match Path::new(arg).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => OUTPUT_EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Split a line into arguments, honouring single and double quotes
+/// and stopping at an unquoted comment. Backslashes are taken literally
+/// except for `\"` inside double quotes.
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            // Backslashes are kept for Windows paths, only an escaped quote is unescaped
+            Some('"') if c == '\\' && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            Some(_) => current.push(c),
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    in_arg = true;
+                }
+                '#' | ';' if !in_arg => break,
+                c if c.is_whitespace() => {
+                    if in_arg {
+                        args.push(std::mem::take(&mut current));
+                        in_arg = false;
+                    }
+                }
+                _ => {
+                    current.push(c);
+                    in_arg = true;
+                }
+            },
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
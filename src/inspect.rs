@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use crate::{parse_symbols, replace_bank, ObjectData, Symbol, BANK_SIZE};
+
+/// An `A` area line from an object file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AreaInfo {
+    pub name: String,
+    /// Bank for banked `_CODE_N` areas
+    pub bank: Option<u32>,
+    pub size: u32,
+    pub flags: u32,
+    pub addr: u32,
+}
+
+/// Everything gbspack reads from an object file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ObjectInfo {
+    pub filename: String,
+    /// Format line such as `XL3`
+    pub version: String,
+    /// Header line such as `H 2 areas 5 global symbols`
+    pub header: String,
+    pub areas: Vec<AreaInfo>,
+    pub symbols: Vec<Symbol>,
+    /// Symbols `replace_bank` rewrites when each banked area is moved
+    pub rewrites: Vec<(u32, Vec<String>)>,
+}
+
+/// Parse an object file into the details shown by `gbspack info`
+pub fn object_info(object: &ObjectData) -> ObjectInfo {
+    let mut lines = object.contents.lines();
+    let version = lines.next().unwrap_or("").trim().to_string();
+    let header = object
+        .contents
+        .lines()
+        .find(|line| line.starts_with("H "))
+        .unwrap_or("")
+        .to_string();
+    let areas: Vec<AreaInfo> = object.contents.lines().filter_map(parse_area).collect();
+
+    let mut rewrites: Vec<(u32, Vec<String>)> = vec![];
+    for area in object.banks.iter() {
+        if !rewrites.iter().any(|(bank, _)| *bank == area.bank) {
+            rewrites.push((area.bank, rewritten_symbols(&object.contents, area.bank)));
+        }
+    }
+
+    ObjectInfo {
+        filename: object.filename.clone(),
+        version,
+        header,
+        areas,
+        symbols: parse_symbols(&object.contents),
+        rewrites,
+    }
+}
+
+/// Parse any `A` area line, including unbanked areas such as `_CODE` and `_DATA`
+pub fn parse_area(line: &str) -> Option<AreaInfo> {
+    let split = line.split_whitespace().collect::<Vec<&str>>();
+    if split.len() < 8 || split[0] != "A" || split[2] != "size" || split[4] != "flags" || split[6] != "addr" {
+        return None;
+    }
+    Some(AreaInfo {
+        name: split[1].to_string(),
+        bank: split[1].strip_prefix("_CODE_").and_then(|bank| bank.parse::<u32>().ok()),
+        size: u32::from_str_radix(split[3], 16).ok()?,
+        flags: u32::from_str_radix(split[5], 16).ok()?,
+        addr: u32::from_str_radix(split[7], 16).ok()?,
+    })
+}
+
+/// Find the symbols `replace_bank` would rewrite when moving an object's
+/// areas out of `bank`, by comparing the symbols before and after
+pub fn rewritten_symbols(contents: &str, bank: u32) -> Vec<String> {
+    let other_bank = if bank == 1 { 2 } else { 1 };
+    let before = parse_symbols(contents);
+    let after = parse_symbols(&replace_bank(contents, bank, other_bank));
+    before
+        .iter()
+        .zip(after.iter())
+        .filter(|(a, b)| a != b)
+        .map(|(a, _)| a.name.clone())
+        .collect()
+}
+
+/// Objects placed in a bank of packed output
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BankUsage {
+    pub bank: u32,
+    /// Filename and size of each area in the bank
+    pub areas: Vec<(String, u32)>,
+}
+
+impl BankUsage {
+    pub fn size(&self) -> u32 {
+        self.areas.iter().fold(0, |a, b| a + b.1)
+    }
+
+    pub fn free(&self) -> i64 {
+        BANK_SIZE as i64 - self.size() as i64
+    }
+}
+
+/// Group the banked areas of packed object files by bank
+pub fn bank_usage(objects: &[ObjectData]) -> Vec<BankUsage> {
+    let mut banks: BTreeMap<u32, BankUsage> = BTreeMap::new();
+    for object in objects {
+        for area in object.banks.iter() {
+            banks
+                .entry(area.bank)
+                .or_insert(BankUsage { bank: area.bank, areas: vec![] })
+                .areas
+                .push((object.filename.clone(), area.size));
+        }
+    }
+    banks.into_iter().map(|(_, bank)| bank).collect()
+}
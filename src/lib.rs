@@ -1,19 +1,99 @@
 use regex::Captures;
 use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{Error, ErrorKind};
 use std::path::Path;
-use std::io::BufReader;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+use explain::{AreaTrace, AttemptOutcome, BankAttempt};
+use inspect::AreaInfo;
+use options::{PackOptions, Strategy};
+
+pub mod banks;
+pub mod config;
+pub mod emit;
+pub mod explain;
+pub mod input;
+pub mod inspect;
+pub mod linker;
+pub mod linkmap;
+pub mod lock;
+pub mod options;
+pub mod reserve;
+pub mod save;
+pub mod verify;
+
+#[derive(Debug, Clone, Default)]
 pub struct Bank {
     pub objects: Vec<(usize, ObjectBankData)>,
+    /// Bytes already used by contents gbspack doesn't pack, such as library code
+    pub existing: u32,
+    /// First bank of a spanning area which runs on through this bank
+    pub spanned_from: Option<u32>,
+}
+
+impl Bank {
+    /// Total bytes used in the bank
+    pub fn size(&self) -> u32 {
+        self.objects.iter().fold(self.existing, |a, b| a + b.1.footprint())
+    }
 }
 
-#[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Clone, Copy)]
+/// Area flag for an absolute area, which the linker places at its own address
+pub const AREA_ABS: u32 = 0x08;
+/// Area flag for a paged area, which must start on a 256 byte page boundary
+pub const AREA_PAG: u32 = 0x10;
+
+#[derive(Debug, Default, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub struct ObjectBankData {
     pub size: u32,
     pub bank: u32,
+    /// Area flags from the `A` line
+    pub flags: u32,
+    /// Address from the `A` line, 0 for relocatable areas
+    pub addr: u32,
+}
+
+impl ObjectBankData {
+    /// A relocatable area of `size` bytes in `bank`
+    pub fn new(size: u32, bank: u32) -> ObjectBankData {
+        ObjectBankData {
+            size,
+            bank,
+            ..Default::default()
+        }
+    }
+
+    /// Absolute areas and areas with a fixed address can't be moved
+    pub fn is_absolute(&self) -> bool {
+        self.flags & AREA_ABS != 0 || self.addr != 0
+    }
+
+    /// Boundary the linker starts the area on
+    pub fn alignment(&self) -> u32 {
+        if self.flags & AREA_PAG != 0 {
+            0x100
+        } else {
+            1
+        }
+    }
+
+    /// Most bytes of padding the linker may add before the area to align it
+    pub fn padding(&self) -> u32 {
+        if self.size == 0 {
+            0
+        } else {
+            self.alignment() - 1
+        }
+    }
+
+    /// Bytes the area takes in a bank, including alignment padding
+    pub fn footprint(&self) -> u32 {
+        self.size + self.padding()
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, PartialOrd, Ord, Clone)]
@@ -23,7 +103,15 @@ pub struct ObjectData {
     pub contents: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    /// True for a definition, false for a reference to another object
+    pub def: bool,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone)]
 pub struct BankReplacement {
     pub from: u32,
     pub to: u32,
@@ -36,7 +124,117 @@ pub struct ObjectPatch {
     pub replacements: Vec<BankReplacement>,
 }
 
-const BANK_SIZE: u32 = 16384;
+/// Where a single area of an object was placed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AreaPlacement {
+    /// Index of the object in the packed objects
+    pub object: usize,
+    /// Index of the area within the object
+    pub area: usize,
+    /// Bank of the area in the input object file
+    pub from: u32,
+    /// Bank the area was packed into
+    pub bank: u32,
+    pub size: u32,
+    /// Most bytes of padding the linker may add before the area to align it
+    pub padding: u32,
+    /// Estimated offset of the area from the start of its bank when the objects
+    /// are linked in order after the bank's existing bytes and kept areas, or
+    /// the area's own address for absolute areas. Areas moved into ROM0 start
+    /// after the space already used there
+    pub offset: u32,
+}
+
+/// Contents of a bank after packing, numbered as it will be in the output
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackedBank {
+    pub bank: u32,
+    pub areas: Vec<AreaPlacement>,
+    /// Bytes already used by contents gbspack doesn't pack
+    pub existing: u32,
+    /// Bytes kept free by reserve rules
+    pub reserved: u32,
+    /// Bytes which can be packed into the bank
+    pub capacity: u32,
+    /// First bank of a spanning area which runs on through this bank
+    pub spanned_from: Option<u32>,
+}
+
+impl PackedBank {
+    /// Total bytes used in the bank
+    pub fn size(&self) -> u32 {
+        self.areas.iter().fold(self.existing, |a, b| a + b.size + b.padding)
+    }
+
+    /// Bytes still free in the bank after reservations
+    pub fn free(&self) -> u32 {
+        self.capacity.saturating_sub(self.size() + self.reserved)
+    }
+}
+
+/// Full result of a packing run
+#[derive(Debug)]
+pub struct PackResult {
+    /// Every bank from bank 1 up to the last one used, including banks
+    /// left empty by the bank offset or reserve rules
+    pub banks: Vec<PackedBank>,
+    /// Placement of every area, in object and area order, where
+    /// areas moved into ROM0 have bank 0
+    pub placements: Vec<AreaPlacement>,
+    /// Bank replacements to apply to each object
+    pub patches: Vec<ObjectPatch>,
+    pub max_bank: u32,
+    pub cart_size: u32,
+}
+
+impl PackResult {
+    /// Placements of the areas of a single object
+    pub fn object_placements(&self, object: usize) -> impl Iterator<Item = &AreaPlacement> {
+        self.placements.iter().filter(move |placement| placement.object == object)
+    }
+}
+
+/// Memory bank controller used by the cartridge
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapper {
+    Mbc1,
+    Mbc3,
+    Mbc5,
+}
+
+impl FromStr for Mapper {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "mbc1" => Ok(Mapper::Mbc1),
+            "mbc3" => Ok(Mapper::Mbc3),
+            "mbc5" => Ok(Mapper::Mbc5),
+            _ => Err(format!("Unknown mapper \"{}\", expected mbc1, mbc3 or mbc5", name)),
+        }
+    }
+}
+
+impl Mapper {
+    /// Number of ROM banks the mapper can address
+    pub fn max_rom_banks(&self) -> u32 {
+        match self {
+            Mapper::Mbc1 | Mapper::Mbc3 => 128,
+            Mapper::Mbc5 => 512,
+        }
+    }
+
+    /// Number of RAM banks the mapper can address
+    pub fn max_ram_banks(&self) -> u32 {
+        match self {
+            Mapper::Mbc1 | Mapper::Mbc3 => 4,
+            Mapper::Mbc5 => 16,
+        }
+    }
+}
+
+pub const BANK_SIZE: u32 = 16384;
 
 /// Read an object file into a struct containing the information required
 /// to pack the data into banks
@@ -52,7 +250,8 @@ pub fn to_object_data(filename: &String) -> std::io::Result<ObjectData> {
 
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    let banks = parse_sizes(&contents);
+    let banks = parse_sizes(&contents)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, format!("Invalid object file \"{}\": {}", filename, err)))?;
 
     Ok(ObjectData {
         filename: filename.to_string(),
@@ -61,34 +260,62 @@ pub fn to_object_data(filename: &String) -> std::io::Result<ObjectData> {
     })
 }
 
-pub fn parse_sizes(contents: &String) -> Vec<ObjectBankData> {
+pub fn parse_sizes(contents: &str) -> Result<Vec<ObjectBankData>, String> {
     let mut banks = Vec::new();
     for line in contents.lines() {
-        if line.contains("A _CODE_") {
-            let parsed_size = parse_size(&line.to_string());
+        if line.starts_with("A _CODE_") {
+            let parsed_size = parse_size(line)?;
             banks.push(parsed_size);
         }
     }
-    banks
+    Ok(banks)
 }
 
-/// Parse the size line from an object file to get the size as an integer
-pub fn parse_size(line: &String) -> ObjectBankData {
-    let split = line.split(" ").collect::<Vec<&str>>();
-    let bank_split = split[1].split("_").collect::<Vec<&str>>();
-    let size = u32::from_str_radix(split[3], 16).unwrap();
-    let bank = bank_split[2].parse::<u32>().unwrap();
-    ObjectBankData { size, bank }
+/// Parse the symbol lines from an object file
+pub fn parse_symbols(contents: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for line in contents.lines() {
+        if let Some(symbol) = line.strip_prefix("S ") {
+            let split = symbol.split(' ').collect::<Vec<&str>>();
+            if split.len() < 2 || split[1].len() < 3 {
+                continue;
+            }
+            let (kind, value) = split[1].split_at(3);
+            if kind != "Def" && kind != "Ref" {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: split[0].to_string(),
+                def: kind == "Def",
+                value: u32::from_str_radix(value, 16).unwrap_or(0),
+            });
+        }
+    }
+    symbols
+}
+
+/// Parse the size line of a banked area from an object file
+pub fn parse_size(line: &str) -> Result<ObjectBankData, String> {
+    match inspect::parse_area(line) {
+        Some(AreaInfo {
+            bank: Some(bank),
+            size,
+            flags,
+            addr,
+            ..
+        }) => Ok(ObjectBankData { size, bank, flags, addr }),
+        _ => Err(format!("Unable to parse banked area line \"{}\"", line)),
+    }
 }
 
 /// Update an object file's contents replacing the bank references with
 /// the specified bank number
-pub fn replace_bank(object_string: &String, original_bank: u32, bank_no: u32) -> String {
-    let mut new_string = object_string.clone();
+pub fn replace_bank(object_string: &str, original_bank: u32, bank_no: u32) -> String {
+    let mut new_string = object_string.to_string();
     // Find banked functions
     for line in object_string.lines() {
-        if line.starts_with("S b_") {
-            let split = line[4..].split(" ").collect::<Vec<&str>>();
+        if let Some(banked_fn) = line.strip_prefix("S b_") {
+            let split = banked_fn.split(' ').collect::<Vec<&str>>();
             let fn_name = split[0];
             let fn_def = format!("S _{}", fn_name);
             // If symbol has pair
@@ -101,7 +328,12 @@ pub fn replace_bank(object_string: &String, original_bank: u32, bank_no: u32) ->
         }
     }
     let find_code = format!("CODE_{}", original_bank);
-    let replace_code = format!("CODE_{}", bank_no);
+    // Areas moved to bank 0 join the unbanked _CODE area in ROM0
+    let replace_code = if bank_no == 0 {
+        "CODE".to_string()
+    } else {
+        format!("CODE_{}", bank_no)
+    };
     let replaced_string = new_string.replace(&find_code, &replace_code);
     let re = Regex::new(&format!("__bank_(?P<s>[^ ]*) Def[0]*{:06X}", original_bank)).unwrap();
     let result = re.replace_all(&replaced_string, |caps: &Captures| {
@@ -110,14 +342,34 @@ pub fn replace_bank(object_string: &String, original_bank: u32, bank_no: u32) ->
     result.to_string()
 }
 
-pub fn replace_all_banks(object_string: &String, replacements: Vec<BankReplacement>) -> String {
+pub fn replace_all_banks(object_string: &str, replacements: Vec<BankReplacement>) -> String {
     replacements
         .into_iter()
-        .fold(object_string.clone(), |string, replacement| {
+        .fold(object_string.to_string(), |string, replacement| {
             replace_bank(&string, replacement.from, replacement.to)
         })
 }
 
+/// Move an object's areas from one bank to another before packing,
+/// when `from` is the filtered bank this pins the object in place
+pub fn pin_object(object: &ObjectData, from: u32, to: u32) -> ObjectData {
+    // Areas moved to bank 0 join the unbanked _CODE area and are no longer banked
+    let banks = object
+        .banks
+        .iter()
+        .filter(|area| area.bank != from || to != 0)
+        .map(|area| ObjectBankData {
+            bank: if area.bank == from { to } else { area.bank },
+            ..*area
+        })
+        .collect();
+    ObjectData {
+        filename: object.filename.clone(),
+        banks,
+        contents: replace_bank(&object.contents, from, to),
+    }
+}
+
 /// Pack an vector of object data into a vector of banks
 /// using a first fit algorithm after sorting the input data
 /// by descending size
@@ -128,131 +380,677 @@ pub fn pack_object_data(
     mbc1: bool,
     reserve: Vec<u32>
 ) -> Vec<ObjectPatch> {
-    let mut banks = Vec::new();
+    let mut builder = PackOptions::builder()
+        .bank_offset(bank_offset.max(1))
+        .mapper(if mbc1 { Mapper::Mbc1 } else { Mapper::Mbc5 })
+        .reserve_sizes(&reserve);
+    if filter != 0 {
+        builder = builder.filter(filter);
+    }
+    match builder.build().and_then(|options| pack_objects(objects, &options)) {
+        Ok(result) => result.patches,
+        Err(err) => panic!("{}", err),
+    }
+}
 
-    let mut areas: Vec<(usize, ObjectBankData)> = objects
-        .clone()
+/// Pack object data into banks, returning the full bank layout along
+/// with the patches for each object. Areas are sorted by descending size
+/// and placed using the strategy in `options`, repacked areas of each group
+/// of objects are kept together in the same bank, and banks are seeded with
+/// the existing bytes used by contents gbspack doesn't pack.
+pub fn pack_objects(objects: Vec<ObjectData>, options: &PackOptions) -> Result<PackResult, String> {
+    let (banks, rom0, _) = pack_banks(&objects, options, None)?;
+
+    let mut packed_banks: Vec<PackedBank> = banks
+        .iter()
+        .enumerate()
+        .map(|(bank_index, bin)| (bank_index as u32 + 1, bin))
+        .map(|(bank_no, bin)| PackedBank {
+            bank: bank_no,
+            areas: vec![],
+            existing: bin.existing,
+            reserved: options.reserved(bank_no),
+            capacity: options.bank_size,
+            spanned_from: bin.spanned_from,
+        })
+        .collect();
+
+    // Match each area of each object to the bank it was packed into, where
+    // an object has identical areas they take the banks in ascending order
+    let mut packed_areas: HashMap<(usize, ObjectBankData), Vec<u32>> = HashMap::new();
+    for (bank_index, bin) in banks.iter().enumerate().rev() {
+        for area in bin.objects.iter().rev() {
+            packed_areas.entry(*area).or_default().push(bank_index as u32 + 1);
+        }
+    }
+    for area in rom0.objects.iter().rev() {
+        packed_areas.entry(*area).or_default().push(0);
+    }
+    let mut placements = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        for (j, area) in object.banks.iter().enumerate() {
+            let bank_no = packed_areas.get_mut(&(i, *area)).and_then(|banks| banks.pop()).unwrap();
+            placements.push(AreaPlacement {
+                object: i,
+                area: j,
+                from: area.bank,
+                bank: bank_no,
+                size: area.size,
+                padding: area.padding(),
+                offset: 0,
+            });
+        }
+    }
+
+    // The linker concatenates the relocatable areas of each bank in link
+    // order, starting each area on its alignment boundary. Contents gbspack
+    // didn't place are taken to come first: the existing bytes, then the
+    // areas kept in their bank, or in ROM0 the space already used
+    let mut offsets: Vec<u32> = (0..=banks.len() as u32).map(|bank_no| options.existing(bank_no)).collect();
+    offsets[0] = options.bank_size.saturating_sub(options.rom0_free);
+    let (kept, moved): (Vec<usize>, Vec<usize>) = (0..placements.len()).partition(|&index| {
+        let placement = &placements[index];
+        options.is_fixed(&objects[placement.object].banks[placement.area])
+    });
+    for index in kept.into_iter().chain(moved) {
+        let placement = &mut placements[index];
+        let area = &objects[placement.object].banks[placement.area];
+        if area.is_absolute() {
+            placement.offset = area.addr.saturating_sub(linker::BANKED_BASE);
+            continue;
+        }
+        let alignment = area.alignment();
+        let offset = (offsets[placement.bank as usize] + alignment - 1) / alignment * alignment;
+        offsets[placement.bank as usize] = offset + area.size;
+        placement.offset = offset;
+    }
+    for placement in placements.iter().filter(|placement| placement.bank > 0) {
+        packed_banks[placement.bank as usize - 1].areas.push(*placement);
+    }
+
+    // Spanning areas take every bank up to the end of their run
+    let max_bank = placements
+        .iter()
+        .map(|placement| placement.bank + placement.size.saturating_sub(1) / options.bank_size)
+        .max()
+        .unwrap_or(0);
+    if max_bank > options.max_bank {
+        return Err(format!(
+            "Packed data needs bank {} but the max bank is {}",
+            max_bank, options.max_bank
+        ));
+    }
+
+    // Convert packed data into object patch
+    let patches = objects
         .into_iter()
         .enumerate()
-        .map(|(i, x)| x.banks.into_iter().map(move |y| (i, y)))
-        .flatten()
+        .map(|(i, x)| ObjectPatch {
+            filename: x.filename,
+            contents: x.contents,
+            replacements: get_bank_replacements(i, &banks, &rom0),
+        })
         .collect();
 
-    // Sort objects by descending size
-    areas.sort_by(|a, b| b.1.size.cmp(&a.1.size));
+    Ok(PackResult {
+        banks: packed_banks,
+        placements,
+        patches,
+        max_bank,
+        cart_size: to_cart_size(max_bank),
+    })
+}
+
+/// Pack object data as with `pack_objects`, tracing each bank the
+/// packer tried while placing the areas of the object at `index`
+pub fn explain_object(objects: Vec<ObjectData>, options: &PackOptions, index: usize) -> Result<Vec<AreaTrace>, String> {
+    let (_, _, traces) = pack_banks(&objects, options, Some(index))?;
+    Ok(traces)
+}
+
+/// Banks tried while placing a unit, only recorded when it is being explained
+struct Attempts {
+    traced: bool,
+    required: u32,
+    capacity: u32,
+    attempts: Vec<BankAttempt>,
+}
+
+impl Attempts {
+    fn push(&mut self, bank: u32, used: u32, reserved: u32, outcome: AttemptOutcome) {
+        if self.traced {
+            self.attempts.push(BankAttempt {
+                bank,
+                used,
+                reserved,
+                required: self.required,
+                capacity: self.capacity,
+                outcome,
+            });
+        }
+    }
+}
+
+fn pack_banks(
+    objects: &[ObjectData],
+    options: &PackOptions,
+    explain: Option<usize>
+) -> Result<(Vec<Bank>, Bank, Vec<AreaTrace>), String> {
+    let bank_size = options.bank_size;
+    let mut banks = Vec::new();
+    let mut traces = Vec::new();
 
-    let max_size = areas.iter().map(|a| a.1.size).max().unwrap();
+    let mut areas: Vec<(usize, ObjectBankData)> = objects
+        .iter()
+        .enumerate()
+        .flat_map(|(i, x)| x.banks.iter().map(move |y| (i, *y)))
+        .collect();
+    // Sort objects by descending size
+    areas.sort_by_key(|a| std::cmp::Reverse(a.1.footprint()));
 
-    if BANK_SIZE < max_size {
-        panic!("Object file too large to fit in bank.");
+    for area in areas.iter().take_while(|area| area.1.footprint() > bank_size) {
+        if !options.is_spanning(area.0) {
+            return Err(format!(
+                "Object file {} too large to fit in bank. Size was {} bytes where max allowed is {} bytes",
+                objects[area.0].filename, area.1.footprint(), bank_size
+            ));
+        }
+        if options.is_fixed(&area.1) {
+            return Err(format!(
+                "Object file {} has an area of {} bytes fixed in bank {}, only repacked areas can span banks",
+                objects[area.0].filename, area.1.size, area.1.bank
+            ));
+        }
+    }
+    for group in options.groups.iter() {
+        if let Some(index) = group.iter().find(|&&index| index >= objects.len()) {
+            return Err(format!("Group refers to object {} but only {} objects were given", index, objects.len()));
+        }
+        if let Some(&index) = group.iter().find(|&&index| options.is_spanning(index)) {
+            return Err(format!(
+                "Object file {} spans banks so can't be grouped with other objects",
+                objects[index].filename
+            ));
+        }
+    }
+    let mut sequenced: Vec<usize> = vec![];
+    for sequence in options.sequences.iter() {
+        for &index in sequence.iter() {
+            if index >= objects.len() {
+                return Err(format!("Sequence refers to object {} but only {} objects were given", index, objects.len()));
+            }
+            if sequenced.contains(&index)
+                || options.is_spanning(index)
+                || options.groups.iter().any(|group| group.contains(&index))
+            {
+                return Err(format!(
+                    "Object file {} is in a sequence so can't be in another sequence, a group or span banks",
+                    objects[index].filename
+                ));
+            }
+            sequenced.push(index);
+        }
     }
 
     // Add the extra banks first
-    let arr = vec![Bank { objects: vec![] }; bank_offset as usize];
+    let arr = vec![Bank::default(); options.bank_offset as usize];
     banks.extend_from_slice(&arr);
 
     // Pack fixed areas
-    if filter != 0 {
-        for area in areas.iter() {
-            if area.1.bank != filter {
-                let size_diff: i32 = (area.1.bank as i32) - (banks.len() as i32);
-                if size_diff > 0 {
-                    // Add the extra banks first
-                    let arr = vec![Bank { objects: vec![] }; size_diff as usize];
-                    banks.extend_from_slice(&arr);
-                }
-                banks[(area.1.bank - 1) as usize].objects.push(area.clone());
+    for area in areas.iter() {
+        if options.is_fixed(&area.1) {
+            if options.is_autobanked(area.1.bank)
+                && objects[area.0].banks.iter().any(|other| other.bank == area.1.bank && !options.is_fixed(other))
+            {
+                return Err(format!(
+                    "Object file {} has an absolute area in bank {} which can't be moved along with its other areas",
+                    objects[area.0].filename, area.1.bank
+                ));
+            }
+            if area.1.bank == 0 || area.1.bank > options.max_bank {
+                return Err(format!(
+                    "Object file {} has an area fixed in bank {} which is out of range (1-{})",
+                    objects[area.0].filename, area.1.bank, options.max_bank
+                ));
+            }
+            if options.is_denied(area.1.bank) {
+                return Err(format!(
+                    "Object file {} has an area fixed in bank {} which is denied",
+                    objects[area.0].filename, area.1.bank
+                ));
+            }
+            let size_diff: i32 = (area.1.bank as i32) - (banks.len() as i32);
+            if size_diff > 0 {
+                // Add the extra banks first
+                let arr = vec![Bank::default(); size_diff as usize];
+                banks.extend_from_slice(&arr);
             }
+            banks[(area.1.bank - 1) as usize].objects.push(*area);
+            if explain == Some(area.0) {
+                traces.push(AreaTrace {
+                    from: area.1.bank,
+                    size: area.1.size,
+                    position: None,
+                    count: 0,
+                    attempts: vec![],
+                    bank: area.1.bank,
+                });
+            }
+        }
+    }
+
+    // Seed banks with contents gbspack doesn't pack
+    if let Some(last_used) = options.existing.iter().rposition(|&size| size > 0) {
+        if banks.len() < last_used {
+            banks.resize(last_used, Bank::default());
+        }
+        for (bank_index, bank) in banks.iter_mut().enumerate() {
+            bank.existing = options.existing(bank_index as u32 + 1);
         }
     }
 
     // Check fixed areas are within max size
     for (bank_index, bank) in banks.iter().enumerate() {
-        let size = bank.objects.iter().fold(0, |a, b| a + b.1.size);
-        let reserved = reserve[bank_index + 1];
-        if size + reserved > BANK_SIZE {
-            panic!(
+        let size = bank.size();
+        let reserved = options.reserved(bank_index as u32 + 1);
+        if size + reserved > bank_size {
+            return Err(format!(
                 "Bank overflow in {}. Size was {} bytes where max allowed is {} bytes",
                 bank_index + 1,
                 size,
-                BANK_SIZE
-            );
+                bank_size - reserved
+            ));
         }
     }
 
-    // Pack unfixed areas
+    // Place sequences first so they get runs of consecutive banks
+    let count = options.sequences.len();
+    for (position, sequence) in options.sequences.iter().enumerate() {
+        let sizes: Vec<u32> = sequence
+            .iter()
+            .map(|&index| {
+                objects[index]
+                    .banks
+                    .iter()
+                    .filter(|area| !options.is_fixed(area))
+                    .fold(0, |size, area| size + area.footprint())
+            })
+            .collect();
+        let first = match place_sequence(&banks, sequence, &sizes, options) {
+            Some(first) => first,
+            None => {
+                let filenames: Vec<&str> = sequence.iter().map(|&index| objects[index].filename.as_str()).collect();
+                return Err(format!(
+                    "Unable to fit sequence {} in {} consecutive banks up to max bank {}",
+                    filenames.join(","),
+                    sequence.len(),
+                    options.max_bank
+                ));
+            }
+        };
+        let last = first + sequence.len() as u32 - 1;
+        if banks.len() < last as usize {
+            banks.resize(last as usize, Bank::default());
+        }
+        for (i, &index) in sequence.iter().enumerate() {
+            let bank_no = first + i as u32;
+            let bank = &mut banks[bank_no as usize - 1];
+            let used = bank.size();
+            for area in areas.iter().filter(|area| area.0 == index && !options.is_fixed(&area.1)) {
+                bank.objects.push(*area);
+                if explain == Some(index) {
+                    traces.push(AreaTrace {
+                        from: area.1.bank,
+                        size: area.1.size,
+                        position: Some(position + 1),
+                        count,
+                        attempts: vec![BankAttempt {
+                            bank: bank_no,
+                            used,
+                            reserved: options.reserved(bank_no),
+                            required: sizes[i],
+                            capacity: bank_size,
+                            outcome: AttemptOutcome::Placed,
+                        }],
+                        bank: bank_no,
+                    });
+                }
+            }
+        }
+    }
+
+    // An object's areas from the same bank join the _CODE area together,
+    // so each (object, bank) pair is moved into ROM0 whole
+    let mut object_units: Vec<Vec<(usize, ObjectBankData)>> = vec![];
+    let mut unit_keys: HashMap<(usize, u32), usize> = HashMap::new();
     for area in areas.iter() {
-        if filter == 0 || area.1.bank == filter {
-            let mut stored = false;
+        if !options.is_fixed(&area.1) && !sequenced.contains(&area.0) {
+            match unit_keys.get(&(area.0, area.1.bank)) {
+                Some(&unit_index) => object_units[unit_index].push(*area),
+                None => {
+                    unit_keys.insert((area.0, area.1.bank), object_units.len());
+                    object_units.push(vec![*area]);
+                }
+            }
+        }
+    }
 
-            // Find first fit in existing banks
-            let mut bank_no = 0;
-            for bank in &mut banks {
-                bank_no += 1;
+    // Move the smallest unbanked units into the free space in ROM0
+    let mut rom0 = Bank::default();
+    let mut promoted = vec![false; object_units.len()];
+    let mut candidates: Vec<usize> = (0..object_units.len())
+        .filter(|&unit_index| {
+            let index = object_units[unit_index][0].0;
+            options.is_unbanked(index)
+                && !options.is_spanning(index)
+                && !options.groups.iter().any(|group| group.contains(&index))
+        })
+        .collect();
+    candidates.sort_by_key(|&unit_index| unit_size(&object_units[unit_index]));
+    for unit_index in candidates {
+        let unit = &object_units[unit_index];
+        if rom0.size() + unit_size(unit) > options.rom0_free {
+            break;
+        }
+        rom0.objects.extend_from_slice(unit);
+        promoted[unit_index] = true;
+        for area in unit.iter().filter(|area| Some(area.0) == explain) {
+            traces.push(AreaTrace {
+                from: area.1.bank,
+                size: area.1.size,
+                position: None,
+                count: 0,
+                attempts: vec![],
+                bank: 0,
+            });
+        }
+    }
 
-                // Skip until at bank_offset
-                if bank_no < bank_offset {
-                    continue;
-                }
+    // Group unfixed areas into units which must share a bank
+    let mut units: Vec<Vec<(usize, ObjectBankData)>> = vec![];
+    let mut group_units: HashMap<usize, usize> = HashMap::new();
+    for area in areas.iter() {
+        let is_promoted = unit_keys
+            .get(&(area.0, area.1.bank))
+            .map_or(false, |&unit_index| promoted[unit_index]);
+        if !options.is_fixed(&area.1) && !sequenced.contains(&area.0) && !is_promoted {
+            match options.groups.iter().position(|group| group.contains(&area.0)) {
+                Some(group_index) => match group_units.get(&group_index) {
+                    Some(&unit_index) => units[unit_index].push(*area),
+                    None => {
+                        group_units.insert(group_index, units.len());
+                        units.push(vec![*area]);
+                    }
+                },
+                None => units.push(vec![*area]),
+            }
+        }
+    }
+    // Pack each pool in turn, largest units first
+    units.sort_by_key(|unit| (options.pool_of(unit[0].0), std::cmp::Reverse(unit_size(unit))));
 
-                // Calculate current size of bank
-                let res: u32 = bank.objects.iter().fold(0, |a, b| a + b.1.size);
-                let reserved = reserve[bank_no as usize];
+    // Pack unfixed areas
+    for (position, unit) in units.iter().enumerate() {
+        let size = unit_size(unit);
+        if bank_size < size && !options.is_spanning(unit[0].0) {
+            return Err(format!(
+                "Object group too large to fit in bank. Size was {} bytes where max allowed is {} bytes",
+                size, bank_size
+            ));
+        }
+        let mut attempts = Attempts {
+            traced: explain.map_or(false, |index| unit.iter().any(|area| area.0 == index)),
+            required: size,
+            capacity: bank_size,
+            attempts: vec![],
+        };
 
-                // If can fit store it here
-                if (res + area.1.size + reserved) <= BANK_SIZE {
-                    bank.objects.push(area.clone());
-                    stored = true;
-                    break;
-                }
+        // Spill into fallback pools until the unit fits
+        let mut pool = options.pool_of(unit[0].0);
+        let bank_no = loop {
+            if let Some(bank_no) = place_unit(&mut banks, unit, pool, options, &mut attempts) {
+                break bank_no;
             }
-            // No room in existing banks, create a new bank
-            if !stored {
-                let next_reserved = reserve[(bank_no + 1) as usize];
-                if area.1.size + next_reserved > BANK_SIZE {
-                    panic!("Oversized {}", area.1.size);
+            pool = match options.fallback(pool) {
+                Some(fallback) => fallback,
+                None if pool < options.pools.len() => {
+                    return Err(format!(
+                        "Pool \"{}\" is full, unable to fit {} bytes from {} in banks {}",
+                        options.pool_name(pool),
+                        size,
+                        objects[unit[0].0].filename,
+                        options.pools[pool].banks
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "Unable to fit {} bytes from {} in banks up to max bank {}",
+                        size, objects[unit[0].0].filename, options.max_bank
+                    ));
                 }
-                let mut new_bank = Bank { objects: vec![] };
-                new_bank.objects.push(area.clone());
-                banks.push(new_bank);
+            };
+        };
+
+        if attempts.traced {
+            for area in unit.iter().filter(|area| Some(area.0) == explain) {
+                traces.push(AreaTrace {
+                    from: area.1.bank,
+                    size: area.1.size,
+                    position: Some(count + position + 1),
+                    count: count + units.len(),
+                    attempts: attempts.attempts.clone(),
+                    bank: bank_no,
+                });
             }
         }
     }
 
-    // Convert packed data into object patch
-    let patch = objects
-        .into_iter()
-        .enumerate()
-        .map(|(i, x)| ObjectPatch {
-            filename: x.filename,
-            contents: x.contents,
-            replacements: get_bank_replacements(i, &banks, mbc1),
+    Ok((banks, rom0, traces))
+}
+
+/// Place a unit in a bank of a pool, either in the best existing bank for the
+/// strategy or in a new bank, returning the bank number or `None` if the pool is full
+fn place_unit(
+    banks: &mut Vec<Bank>,
+    unit: &[(usize, ObjectBankData)],
+    pool: usize,
+    options: &PackOptions,
+    attempts: &mut Attempts
+) -> Option<u32> {
+    let size = unit_size(unit);
+    let bank_size = options.bank_size;
+    if size > bank_size {
+        return place_span(banks, unit, pool, options, attempts);
+    }
+
+    // Packing downwards considers every bank up to the max bank from the start
+    if options.descending && banks.len() < options.max_bank as usize {
+        banks.resize(options.max_bank as usize, Bank::default());
+    }
+    let order: Vec<usize> = if options.descending {
+        (0..banks.len()).rev().collect()
+    } else {
+        (0..banks.len()).collect()
+    };
+
+    // Find a fit in existing banks
+    let mut chosen: Option<(usize, u32)> = None;
+    for bank_index in order {
+        let bank = &banks[bank_index];
+        let bank_no = bank_index as u32 + 1;
+
+        // Calculate current size of bank
+        let res: u32 = bank.size();
+        let reserved = options.reserved(bank_no);
+
+        // Skip until at bank_offset
+        if bank_no < options.bank_offset {
+            attempts.push(bank_no, res, reserved, AttemptOutcome::BelowOffset);
+            continue;
+        }
+        if bank_no > options.max_bank {
+            continue;
+        }
+        if !options.is_allowed(bank_no) || !options.pool_contains(pool, bank_no) {
+            attempts.push(bank_no, res, reserved, AttemptOutcome::Denied);
+            continue;
+        }
+
+        if bank.spanned_from.is_some() {
+            attempts.push(bank_no, bank_size, reserved, AttemptOutcome::NoSpace);
+        } else if res + size + reserved <= bank_size {
+            let free = bank_size - (res + size + reserved);
+            attempts.push(bank_no, res, reserved, AttemptOutcome::Fits);
+            if options.strategy == Strategy::FirstFit {
+                chosen = Some((bank_index, free));
+                break;
+            }
+            if chosen.map_or(true, |(_, best)| free < best) {
+                chosen = Some((bank_index, free));
+            }
+        } else if res + size <= bank_size {
+            attempts.push(bank_no, res, reserved, AttemptOutcome::Reserved);
+        } else {
+            attempts.push(bank_no, res, reserved, AttemptOutcome::NoSpace);
+        }
+    }
+
+    if let Some((bank_index, _)) = chosen {
+        banks[bank_index].objects.extend_from_slice(unit);
+        let bank_no = bank_index as u32 + 1;
+        if let Some(attempt) = attempts.attempts.iter_mut().rev().find(|attempt| attempt.bank == bank_no) {
+            attempt.outcome = AttemptOutcome::Placed;
+        }
+        return Some(bank_no);
+    }
+
+    // No room in existing banks, create a new bank skipping past
+    // any banks which are outside the pool or reserved in full
+    let first_new = (banks.len() as u32 + 1).max(options.bank_offset);
+    let bank_no = (first_new..=options.max_bank).find(|&bank_no| {
+        options.is_allowed(bank_no)
+            && options.pool_contains(pool, bank_no)
+            && size + options.reserved(bank_no) <= bank_size
+    })?;
+    for skipped in (banks.len() as u32 + 1)..bank_no {
+        banks.push(Bank::default());
+        let reserved = options.reserved(skipped);
+        if skipped < options.bank_offset {
+            attempts.push(skipped, 0, reserved, AttemptOutcome::BelowOffset);
+        } else if !options.is_allowed(skipped) || !options.pool_contains(pool, skipped) {
+            attempts.push(skipped, 0, reserved, AttemptOutcome::Denied);
+        } else {
+            attempts.push(skipped, 0, reserved, AttemptOutcome::Reserved);
+        }
+    }
+    let mut new_bank = Bank::default();
+    new_bank.objects.extend_from_slice(unit);
+    banks.push(new_bank);
+    attempts.push(bank_no, 0, options.reserved(bank_no), AttemptOutcome::NewBank);
+    Some(bank_no)
+}
+
+/// Find the first bank of a run of consecutive banks with room for
+/// each object of a sequence in turn
+fn place_sequence(banks: &[Bank], sequence: &[usize], sizes: &[u32], options: &PackOptions) -> Option<u32> {
+    let len = sequence.len() as u32;
+    if len > options.max_bank {
+        return None;
+    }
+    let fits = |first: u32| {
+        sequence.iter().zip(sizes.iter()).enumerate().all(|(i, (&index, &size))| {
+            let bank_no = first + i as u32;
+            let used = banks.get(bank_no as usize - 1).map_or(0, |bank| match bank.spanned_from {
+                Some(_) => options.bank_size,
+                None => bank.size(),
+            });
+            options.is_allowed(bank_no)
+                && options.pool_contains(options.pool_of(index), bank_no)
+                && used + size + options.reserved(bank_no) <= options.bank_size
         })
-        .collect();
+    };
+    let last_first = options.max_bank + 1 - len;
+    if options.descending {
+        (options.bank_offset..=last_first).rev().find(|&first| fits(first))
+    } else {
+        (options.bank_offset..=last_first).find(|&first| fits(first))
+    }
+}
+
+/// Place an area larger than a bank at the start of a run of consecutive empty
+/// banks, returning the first bank of the run or `None` if no run is free. The
+/// whole run is kept for the area as anything linked into the last bank would
+/// overlap the end of it.
+fn place_span(
+    banks: &mut Vec<Bank>,
+    unit: &[(usize, ObjectBankData)],
+    pool: usize,
+    options: &PackOptions,
+    attempts: &mut Attempts
+) -> Option<u32> {
+    let size = unit_size(unit);
+    let bank_size = options.bank_size;
+    let run = (size + bank_size - 1) / bank_size;
+    if run > options.max_bank {
+        return None;
+    }
 
-    patch
+    let is_free = |bank_no: u32, used: u32| {
+        let empty = banks
+            .get(bank_no as usize - 1)
+            .map_or(true, |bank| bank.size() == 0 && bank.spanned_from.is_none());
+        empty
+            && options.is_allowed(bank_no)
+            && options.pool_contains(pool, bank_no)
+            && used + options.reserved(bank_no) <= bank_size
+    };
+    let fits = |first: u32| {
+        (0..run).all(|i| {
+            let used = if i + 1 < run { bank_size } else { size - (run - 1) * bank_size };
+            is_free(first + i, used)
+        })
+    };
+    let last_first = options.max_bank + 1 - run;
+    let first = if options.descending {
+        (options.bank_offset..=last_first).rev().find(|&first| fits(first))
+    } else {
+        (options.bank_offset..=last_first).find(|&first| fits(first))
+    }?;
+
+    let last = first + run - 1;
+    if banks.len() < last as usize {
+        banks.resize(last as usize, Bank::default());
+    }
+    banks[first as usize - 1].objects.extend_from_slice(unit);
+    for bank in banks[first as usize..last as usize].iter_mut() {
+        bank.spanned_from = Some(first);
+    }
+    attempts.push(first, 0, options.reserved(first), AttemptOutcome::Placed);
+    Some(first)
 }
 
-fn get_bank_replacements(index: usize, packed: &Vec<Bank>, mbc1: bool) -> Vec<BankReplacement> {
-    let mut replacements: Vec<BankReplacement> = vec![];
+fn unit_size(unit: &[(usize, ObjectBankData)]) -> u32 {
+    unit.iter().fold(0, |a, b| a + b.1.footprint())
+}
+
+fn get_bank_replacements(index: usize, packed: &[Bank], rom0: &Bank) -> Vec<BankReplacement> {
+    let mut replacements: Vec<BankReplacement> = rom0
+        .objects
+        .iter()
+        .filter(|object| object.0 == index)
+        .map(|object| BankReplacement { from: object.1.bank, to: 0 })
+        .collect();
 
     // Write packed files back to disk
-    let mut bank_no = 1;
-    for bin in packed {
+    for (bank_index, bin) in packed.iter().enumerate() {
         for object in bin.objects.iter() {
-            if mbc1 {
-                if bank_no == 0x20 || bank_no == 0x40 || bank_no == 0x60 {
-                    bank_no += 1;
-                }
-            }
             if object.0 == index {
                 replacements.push(BankReplacement {
                     from: object.1.bank,
-                    to: bank_no,
+                    to: bank_index as u32 + 1,
                 })
             }
         }
-        bank_no += 1;
     }
 
     replacements
@@ -269,7 +1067,7 @@ pub fn to_cart_size(max_bank: u32) -> u32 {
 pub fn to_output_filename(original_filename: &str, output_path: &str, ext: &str) -> String {
     let original_path = Path::new(original_filename);
     let file_stem = original_path.file_stem().unwrap().to_str().unwrap();
-    if output_path.len() > 0 {
+    if !output_path.is_empty() {
         // Store output in dir specified by output_path
         let path = Path::new(&output_path);
         let new_path = path.join(format!("{}.{}", file_stem, ext));
@@ -284,6 +1082,23 @@ pub fn to_output_filename(original_filename: &str, output_path: &str, ext: &str)
     }
 }
 
+/// Check no two input files would be written to the same output file, as
+/// happens when directories searched recursively hold files of the same name
+/// and an output path is given
+pub fn check_output_filenames(filenames: &[String], output_path: &str, ext: &str) -> Result<(), String> {
+    let mut outputs: HashMap<String, &str> = HashMap::new();
+    for filename in filenames {
+        let output_filename = to_output_filename(filename, output_path, ext);
+        if let Some(other) = outputs.insert(output_filename.clone(), filename) {
+            return Err(format!(
+                "Input files {} and {} would both be written to {}",
+                other, filename, output_filename
+            ));
+        }
+    }
+    Ok(())
+}
+
 pub fn get_patch_max_bank(packed: &Vec<ObjectPatch>) -> u32 {
     let mut max = 0;
     for patch in packed {
@@ -295,19 +1110,3 @@ pub fn get_patch_max_bank(packed: &Vec<ObjectPatch>) -> u32 {
     }
     max
 }
-
-/// Load file to vec of filepath strings
-pub fn lines_from_file(filename: &String) -> Vec<String> {
-    let file = match File::open(filename) {
-        Ok(file) => file,
-        Err(err) => {
-            println!("gbspack: Unable to open file \"{}\": {}", filename, err);
-            std::process::exit(1);
-        }
-    };
-
-    let buf = BufReader::new(file);
-    buf.lines()
-        .map(|l| l.expect("Could not parse line"))
-        .collect()
-}
@@ -0,0 +1,78 @@
+use crate::{Mapper, PackedBank};
+
+/// Address the switchable ROM bank is mapped to
+pub const BANKED_BASE: u32 = 0x4000;
+
+/// Cartridge settings passed to the link step
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LinkerArgs {
+    pub mapper: Mapper,
+    /// Number of ROM banks, normally the cart size from `to_cart_size`
+    pub rom_banks: u32,
+    pub ram_banks: u32,
+    pub battery: bool,
+}
+
+impl LinkerArgs {
+    /// Cartridge type byte written to the ROM header
+    pub fn cart_type(&self) -> Result<u8, String> {
+        let ram = self.ram_banks > 0;
+        if self.battery && !ram {
+            return Err("Battery backed cartridges need at least one RAM bank".to_string());
+        }
+        Ok(match (self.mapper, ram, self.battery) {
+            (Mapper::Mbc1, false, _) => 0x01,
+            (Mapper::Mbc1, true, false) => 0x02,
+            (Mapper::Mbc1, true, true) => 0x03,
+            (Mapper::Mbc3, false, _) => 0x11,
+            (Mapper::Mbc3, true, false) => 0x12,
+            (Mapper::Mbc3, true, true) => 0x13,
+            (Mapper::Mbc5, false, _) => 0x19,
+            (Mapper::Mbc5, true, false) => 0x1A,
+            (Mapper::Mbc5, true, true) => 0x1B,
+        })
+    }
+
+    /// Build the `-Wl-yt`, `-Wl-yo` and `-Wl-ya` flags for lcc, checking
+    /// the bank counts are supported by the mapper
+    pub fn to_args(&self) -> Result<Vec<String>, String> {
+        let cart_type = self.cart_type()?;
+        if self.rom_banks > self.mapper.max_rom_banks() {
+            return Err(format!(
+                "{} ROM banks needed but {:?} supports at most {}",
+                self.rom_banks,
+                self.mapper,
+                self.mapper.max_rom_banks()
+            ));
+        }
+        if self.ram_banks > self.mapper.max_ram_banks() {
+            return Err(format!(
+                "{} RAM banks requested but {:?} supports at most {}",
+                self.ram_banks,
+                self.mapper,
+                self.mapper.max_ram_banks()
+            ));
+        }
+        let mut args = vec![
+            format!("-Wl-yt0x{:02X}", cart_type),
+            format!("-Wl-yo{}", self.rom_banks),
+        ];
+        if self.ram_banks > 0 {
+            args.push(format!("-Wl-ya{}", self.ram_banks));
+        }
+        Ok(args)
+    }
+}
+
+/// Build `-Wl-b_CODE_N=0xN4000` flags fixing the base of the `_CODE_N` area of
+/// each bank holding packed areas to the start of the switchable bank, so the
+/// offsets in `AreaPlacement` are measured from a known address. The bank
+/// number sits above the 16-bit address as in the link map, e.g. `_CODE_2`
+/// is linked at `0x24000`.
+pub fn base_address_args(banks: &[PackedBank]) -> Vec<String> {
+    banks
+        .iter()
+        .filter(|bank| !bank.areas.is_empty())
+        .map(|bank| format!("-Wl-b_CODE_{}=0x{:X}", bank.bank, (bank.bank << 16) | BANKED_BASE))
+        .collect()
+}
@@ -0,0 +1,141 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use crate::lock::PackLock;
+use crate::{ObjectData, BANK_SIZE};
+
+/// An area from a linker `.map` or `.noi` file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MapArea {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Parse the area table of an sdldgb `.map` file, where each area is listed as
+/// `_CODE_1    00014000    000012A3 =    4771. bytes (REL,CON)`
+pub fn parse_map(contents: &str) -> Vec<MapArea> {
+    let re = Regex::new(r"^\s*(_[A-Za-z0-9_]+)\s+([0-9A-Fa-f]+)\s+([0-9A-Fa-f]+)\s+=").unwrap();
+    contents
+        .lines()
+        .filter_map(|line| re.captures(line))
+        .map(|caps| MapArea {
+            name: caps[1].to_string(),
+            addr: u32::from_str_radix(&caps[2], 16).unwrap(),
+            size: u32::from_str_radix(&caps[3], 16).unwrap(),
+        })
+        .collect()
+}
+
+/// Parse the area definitions of an sdldgb `.noi` file, where each area
+/// has a start `DEF s__CODE_1 0x14000` and length `DEF l__CODE_1 0x12A3`
+pub fn parse_noi(contents: &str) -> Vec<MapArea> {
+    let re = Regex::new(r"^DEF ([sl])_(_[A-Za-z0-9_]+) 0x([0-9A-Fa-f]+)").unwrap();
+    let mut areas: Vec<MapArea> = vec![];
+    for caps in contents.lines().filter_map(|line| re.captures(line.trim())) {
+        let value = u32::from_str_radix(&caps[3], 16).unwrap();
+        let index = match areas.iter().position(|area| area.name == caps[2]) {
+            Some(index) => index,
+            None => {
+                areas.push(MapArea { name: caps[2].to_string(), addr: 0, size: 0 });
+                areas.len() - 1
+            }
+        };
+        if &caps[1] == "s" {
+            areas[index].addr = value;
+        } else {
+            areas[index].size = value;
+        }
+    }
+    areas
+}
+
+/// Read the areas from a `.map` or `.noi` file, picking the parser from the extension
+pub fn read_link_map(filename: &str) -> std::io::Result<Vec<MapArea>> {
+    let contents = fs::read_to_string(filename)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to open file \"{}\": {}", filename, err)))?;
+    let is_noi = Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("noi"));
+    if is_noi {
+        Ok(parse_noi(&contents))
+    } else {
+        Ok(parse_map(&contents))
+    }
+}
+
+/// Get the bank number of a banked code area such as `_CODE_12`
+pub fn area_bank(name: &str) -> Option<u32> {
+    name.strip_prefix("_CODE_").and_then(|bank| bank.parse::<u32>().ok())
+}
+
+/// Total bytes used in each switchable bank by the banked code areas of a link map
+pub fn bank_usage(areas: &[MapArea]) -> BTreeMap<u32, u32> {
+    let mut usage = BTreeMap::new();
+    for area in areas {
+        if let Some(bank) = area_bank(&area.name) {
+            *usage.entry(bank).or_insert(0) += area.size;
+        }
+    }
+    usage
+}
+
+/// Bytes each bank held from `objects` when the link map was linked, taken from
+/// the banks `lock` recorded for them or from their own banks when an object
+/// isn't in the lock. Areas moved into ROM0 are counted in bank 0
+pub fn linked_usage(objects: &[ObjectData], lock: Option<&PackLock>) -> BTreeMap<u32, u32> {
+    let mut usage = BTreeMap::new();
+    for object in objects {
+        let locked = lock.and_then(|lock| lock.objects.iter().find(|locked| locked.filename == object.filename));
+        match locked {
+            Some(locked) => {
+                for area in locked.areas.iter() {
+                    *usage.entry(area.bank).or_insert(0) += area.size;
+                }
+            }
+            None => {
+                for area in object.banks.iter() {
+                    *usage.entry(area.bank).or_insert(0) += area.size;
+                }
+            }
+        }
+    }
+    usage
+}
+
+/// Bytes used in each switchable bank of a link map by contents other than
+/// `objects`, such as library code, so repacking the same objects against the
+/// map from their last link doesn't count them twice
+pub fn outside_usage(areas: &[MapArea], objects: &[ObjectData], lock: Option<&PackLock>) -> BTreeMap<u32, u32> {
+    let mut usage = bank_usage(areas);
+    for (bank, size) in linked_usage(objects, lock) {
+        if let Some(used) = usage.get_mut(&bank) {
+            *used = used.saturating_sub(size);
+        }
+    }
+    usage
+}
+
+/// Bytes free at the end of ROM0 after the last area linked there, such as
+/// `_CODE` or `_HOME`
+pub fn rom0_free(areas: &[MapArea]) -> u32 {
+    let end = areas
+        .iter()
+        .filter(|area| area.size > 0 && area.addr + area.size <= BANK_SIZE)
+        .map(|area| area.addr + area.size)
+        .max()
+        .unwrap_or(0);
+    BANK_SIZE - end
+}
+
+/// Bytes free at the end of ROM0 once any areas of `objects` moved there in
+/// the last link are taken back out, so the free space doesn't shrink each
+/// time the same unbanked files are repacked
+pub fn outside_rom0_free(areas: &[MapArea], objects: &[ObjectData], lock: Option<&PackLock>) -> u32 {
+    let linked = linked_usage(objects, lock).get(&0).copied().unwrap_or(0);
+    (rom0_free(areas) + linked).min(BANK_SIZE)
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::{parse_sizes, to_cart_size, ObjectData, ObjectPatch};
+
+/// Record of a packing run, written as JSON so later runs can be compared
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PackLock {
+    pub max_bank: u32,
+    pub cart_size: u32,
+    pub objects: Vec<ObjectLock>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ObjectLock {
+    pub filename: String,
+    pub areas: Vec<AreaLock>,
+}
+
+/// Placement of a single banked area
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AreaLock {
+    /// Bank of the area in the input object file
+    pub from: u32,
+    /// Bank the area was packed into
+    pub bank: u32,
+    pub size: u32,
+}
+
+impl PackLock {
+    /// Build a lock from the patches returned by `pack_object_data`,
+    /// taking each area's final bank from the first replacement of its bank
+    /// in the same way as `replace_all_banks`
+    pub fn from_patches(packed: &[ObjectPatch], max_bank: u32) -> Result<PackLock, String> {
+        let mut objects = vec![];
+        for patch in packed {
+            let areas = parse_sizes(&patch.contents)
+                .map_err(|err| format!("Invalid object file \"{}\": {}", patch.filename, err))?;
+            objects.push(ObjectLock {
+                filename: patch.filename.clone(),
+                areas: areas
+                    .iter()
+                    .map(|area| AreaLock {
+                        from: area.bank,
+                        bank: patch
+                            .replacements
+                            .iter()
+                            .find(|replacement| replacement.from == area.bank)
+                            .map_or(area.bank, |replacement| replacement.to),
+                        size: area.size,
+                    })
+                    .collect(),
+            });
+        }
+        Ok(PackLock {
+            max_bank,
+            cart_size: to_cart_size(max_bank),
+            objects,
+        })
+    }
+
+
+    /// Build a lock from object files which have already been packed
+    pub fn from_objects(objects: &[ObjectData]) -> PackLock {
+        let max_bank = objects
+            .iter()
+            .flat_map(|object| object.banks.iter())
+            .map(|area| area.bank)
+            .max()
+            .unwrap_or(0);
+        PackLock {
+            max_bank,
+            cart_size: to_cart_size(max_bank),
+            objects: objects
+                .iter()
+                .map(|object| ObjectLock {
+                    filename: object.filename.clone(),
+                    areas: object
+                        .banks
+                        .iter()
+                        .map(|area| AreaLock { from: area.bank, bank: area.bank, size: area.size })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Total bytes packed into each bank
+    pub fn bank_fill(&self) -> BTreeMap<u32, u32> {
+        let mut fill = BTreeMap::new();
+        for area in self.objects.iter().flat_map(|object| object.areas.iter()) {
+            *fill.entry(area.bank).or_insert(0) += area.size;
+        }
+        fill
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(contents: &str) -> std::io::Result<PackLock> {
+        serde_json::from_str(contents).map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))
+    }
+}
+
+/// Read a lock file written by `gbspack pack --lock`
+pub fn read_lock(filename: &str) -> std::io::Result<PackLock> {
+    let contents = fs::read_to_string(filename)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to open file \"{}\": {}", filename, err)))?;
+    PackLock::from_json(&contents)
+        .map_err(|err| Error::new(err.kind(), format!("Unable to parse lock file \"{}\": {}", filename, err)))
+}
+
+/// Change to one area between two packing results
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AreaChange {
+    /// File name of the object, used to match objects from different output paths
+    pub name: String,
+    /// Index of the area within the object
+    pub index: usize,
+    pub old: Option<AreaLock>,
+    pub new: Option<AreaLock>,
+}
+
+impl AreaChange {
+    pub fn is_added(&self) -> bool {
+        self.old.is_none()
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.new.is_none()
+    }
+
+    pub fn is_moved(&self) -> bool {
+        matches!((self.old, self.new), (Some(old), Some(new)) if old.bank != new.bank)
+    }
+
+    /// Change in size, positive when the area grew
+    pub fn growth(&self) -> i64 {
+        self.new.map_or(0, |a| a.size as i64) - self.old.map_or(0, |a| a.size as i64)
+    }
+}
+
+/// Change in the bytes packed into a bank
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BankChange {
+    pub bank: u32,
+    pub old_size: u32,
+    pub new_size: u32,
+}
+
+/// Explanation of the differences between two packing results
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackDiff {
+    pub old_max_bank: u32,
+    pub new_max_bank: u32,
+    /// Areas which were added, removed, moved or changed size
+    pub areas: Vec<AreaChange>,
+    /// Banks whose fill changed
+    pub banks: Vec<BankChange>,
+    /// For each bank beyond the old max bank, the new or grown objects placed in it
+    pub new_banks: Vec<(u32, Vec<String>)>,
+}
+
+/// Compare two packing results. Objects are matched by file name so results
+/// written to different output paths can be compared, and two objects with
+/// the same file name in one result are reported as an error.
+pub fn diff_locks(old: &PackLock, new: &PackLock) -> Result<PackDiff, String> {
+    let old_areas = areas_by_name(old)?;
+    let new_areas = areas_by_name(new)?;
+    let names: BTreeSet<&String> = old_areas.keys().chain(new_areas.keys()).collect();
+
+    let mut areas = vec![];
+    for name in names {
+        let empty = vec![];
+        let old_object = old_areas.get(name).unwrap_or(&empty);
+        let new_object = new_areas.get(name).unwrap_or(&empty);
+        for index in 0..old_object.len().max(new_object.len()) {
+            let change = AreaChange {
+                name: name.clone(),
+                index,
+                old: old_object.get(index).copied(),
+                new: new_object.get(index).copied(),
+            };
+            if change.is_added() || change.is_removed() || change.is_moved() || change.growth() != 0 {
+                areas.push(change);
+            }
+        }
+    }
+
+    let old_fill = old.bank_fill();
+    let new_fill = new.bank_fill();
+    let bank_numbers: BTreeSet<&u32> = old_fill.keys().chain(new_fill.keys()).collect();
+    let banks = bank_numbers
+        .into_iter()
+        .map(|&bank| BankChange {
+            bank,
+            old_size: old_fill.get(&bank).copied().unwrap_or(0),
+            new_size: new_fill.get(&bank).copied().unwrap_or(0),
+        })
+        .filter(|change| change.old_size != change.new_size)
+        .collect();
+
+    let mut new_banks = vec![];
+    for bank in (old.max_bank + 1)..=new.max_bank {
+        let mut triggers: Vec<String> = areas
+            .iter()
+            .filter(|change| change.new.map_or(false, |area| area.bank == bank))
+            .filter(|change| change.is_added() || change.growth() > 0)
+            .map(|change| change.name.clone())
+            .collect();
+        triggers.dedup();
+        new_banks.push((bank, triggers));
+    }
+
+    Ok(PackDiff {
+        old_max_bank: old.max_bank,
+        new_max_bank: new.max_bank,
+        areas,
+        banks,
+        new_banks,
+    })
+}
+
+fn areas_by_name(lock: &PackLock) -> Result<BTreeMap<String, Vec<AreaLock>>, String> {
+    let mut objects: BTreeMap<String, (&str, Vec<AreaLock>)> = BTreeMap::new();
+    for object in lock.objects.iter() {
+        let name = Path::new(&object.filename)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&object.filename)
+            .to_string();
+        if let Some((other, _)) = objects.insert(name.clone(), (&object.filename, object.areas.clone())) {
+            return Err(format!(
+                "Objects {} and {} have the same file name {} so can't be told apart when comparing",
+                other, object.filename, name
+            ));
+        }
+    }
+    Ok(objects.into_iter().map(|(name, (_, areas))| (name, areas)).collect())
+}
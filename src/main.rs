@@ -1,13 +1,102 @@
-use clap::{value_t, values_t, App, Arg};
+use clap::{value_t, values_t, App, Arg, ArgMatches, SubCommand};
 use std::fs::File;
 use std::io::prelude::*;
-use gbspacklib;
 
 fn main() -> std::io::Result<()> {
-  let matches = App::new("GBStudio Pack")
+  let app = App::new("GBStudio Pack")
     .version("1.2.9")
     .author("Chris Maltby. <chris.maltby@gmail.com>")
-    .about("Packs object files created by GB Studio data into banks")
+    .about("Packs object files created by GB Studio data into banks");
+  let matches = with_pack_args(app)
+    .subcommand(with_pack_args(
+      SubCommand::with_name("pack").about("Packs object files into banks (default when no subcommand is given)"),
+    ))
+    .subcommand(
+      SubCommand::with_name("info")
+        .about("Shows the header, areas and symbols parsed from object files")
+        .arg(
+          Arg::with_name("INPUT")
+            .help("Sets the .o files to inspect")
+            .required(true)
+            .multiple(true)
+            .index(1),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("verify")
+        .about("Checks packed object files against the map file from the final link")
+        .arg(
+          Arg::with_name("map")
+            .short("m")
+            .long("map")
+            .value_name("FILE")
+            .help("Sets the .map or .noi file generated by the linker")
+            .takes_value(true)
+            .required(true),
+        )
+        .arg(
+          Arg::with_name("input_file")
+            .short("i")
+            .long("input")
+            .help("Optionally specify a file containing the packed .o files")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("INPUT")
+            .help("Sets the packed .o files to check")
+            .multiple(true)
+            .index(1),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("map")
+        .about("Shows the bank usage of packed object files")
+        .arg(
+          Arg::with_name("input_file")
+            .short("i")
+            .long("input")
+            .help("Optionally specify a file containing the packed .o files")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("INPUT")
+            .help("Sets the packed .o files to show")
+            .multiple(true)
+            .index(1),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("diff")
+        .about("Explains the differences between two packing results")
+        .arg(
+          Arg::with_name("OLD")
+            .help("Sets the previous lock file, or packed .o files as a directory, glob pattern or @file")
+            .required(true)
+            .index(1),
+        )
+        .arg(
+          Arg::with_name("NEW")
+            .help("Sets the new lock file, or packed .o files as a directory, glob pattern or @file")
+            .required(true)
+            .index(2),
+        ),
+    )
+    .get_matches();
+
+  match matches.subcommand() {
+    ("pack", Some(pack_matches)) => pack(pack_matches),
+    ("info", Some(info_matches)) => info(info_matches),
+    ("verify", Some(verify_matches)) => verify(verify_matches),
+    ("map", Some(map_matches)) => map(map_matches),
+    ("diff", Some(diff_matches)) => diff(diff_matches),
+    _ => pack(&matches),
+  }
+}
+
+/// Add the packing options, shared by the `pack` subcommand and
+/// flag-only invocations from older builds
+fn with_pack_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+  app
     .arg(
       Arg::with_name("offset")
         .short("b")
@@ -20,8 +109,8 @@ fn main() -> std::io::Result<()> {
       Arg::with_name("filter")
       .short("f")
       .long("filter")
-      .value_name("NN")
-      .help("Only repack files from specified bank (default repack all banks)")
+      .value_name("BANKS")
+      .help("Only repack files from specified banks, a list or range such as 254,255 or 250-255 (default repack all banks)")
       .takes_value(true),
     )
     .arg(
@@ -32,18 +121,192 @@ fn main() -> std::io::Result<()> {
       .help("Reserve N additional banks at end of cart for batteryless saving (default 0)")
       .takes_value(true),
     )
+    .arg(
+      Arg::with_name("batteryless")
+        .long("batteryless")
+        .value_name("SIZE")
+        .help("Keep a sector aligned block of banks free at the end of cart for a batteryless save of SIZE bytes e.g. 32K")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("flash_sector")
+        .long("flash-sector")
+        .value_name("SIZE")
+        .help("Sets the flash sector size the batteryless save block is aligned to (default 64K)")
+        .takes_value(true),
+    )
     .arg(
       Arg::with_name("reserve_space")
         .long("reserve")
         .short("s")
-        .help("Optionally reserve space in banks using format 1:7F3,2:00F")
-        .takes_value(true),
+        .help("Optionally reserve space in banks using format 1:7F3,2:00F (banks may be a range 1-8 or all, size may be a percentage 10% or full)")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
     )
     .arg(
       Arg::with_name("mbc1")
         .long("mbc1")
         .help("Use MBC1 hardware (skip banks 0x20, 0x40 and 0x60)"),
     )
+    .arg(
+      Arg::with_name("mapper")
+        .long("mapper")
+        .value_name("MBC")
+        .help("Sets the cartridge mapper, one of mbc1, mbc3 or mbc5 (default mbc5)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("link_map")
+        .long("link-map")
+        .value_name("FILE")
+        .help("Seed banks with the space already used in a .map or .noi file from a previous link")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("linker_args")
+        .long("linker-args")
+        .value_name("FILE")
+        .help("Write a linker options file with the cart type and ROM/RAM bank counts")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("ram_banks")
+        .long("ram-banks")
+        .value_name("NN")
+        .help("Sets the number of cartridge RAM banks for the linker options file (default 0)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("base_addresses")
+        .long("base-addresses")
+        .help("Fix the base of each packed bank in the linker options file so areas land near the offsets gbspack estimated"),
+    )
+    .arg(
+      Arg::with_name("battery")
+        .long("battery")
+        .help("Use a battery backed cartridge type in the linker options file"),
+    )
+    .arg(
+      Arg::with_name("emit_header")
+        .long("emit-header")
+        .value_name("FILE")
+        .help("Write a C header defining the packed bank of each ___bank_ symbol")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("emit_asm")
+        .long("emit-asm")
+        .value_name("FILE")
+        .help("Write an assembly include defining the packed bank of each ___bank_ symbol")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("asm_syntax")
+        .long("asm-syntax")
+        .value_name("SYNTAX")
+        .help("Sets the assembly include syntax, one of asxxxx or rgbds (default asxxxx)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("lock_file")
+        .long("lock")
+        .value_name("FILE")
+        .help("Write a JSON record of where each area was packed, for use with gbspack diff")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("allow_banks")
+        .long("allow-banks")
+        .value_name("BANKS")
+        .help("Only pack into these banks, a list or range such as 5-63,80")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("deny_banks")
+        .long("deny-banks")
+        .value_name("BANKS")
+        .help("Never pack into these banks, a list or range such as 1-4,120-127")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("pool")
+        .long("pool")
+        .value_name("POOL")
+        .help("Pack matching files in their own banks using format name:banks:files[:fallback] e.g. music:8-15:music_*.o")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("span")
+        .long("span")
+        .value_name("FILES")
+        .help("Allow data-only files to have areas larger than a bank, packed into a run of consecutive banks e.g. pcm_*.o")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true),
+    )
+    .arg(
+      Arg::with_name("sequence")
+        .long("sequence")
+        .value_name("FILES")
+        .help("Pack the listed files into consecutive banks in order e.g. song_1.o,song_2.o,song_3.o")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("unbanked")
+        .long("unbanked")
+        .value_name("FILES")
+        .help("Files the engine can read without switching banks, whose smallest areas are moved into free ROM0 space")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true),
+    )
+    .arg(
+      Arg::with_name("rom0_free")
+        .long("rom0-free")
+        .value_name("SIZE")
+        .help("Sets the bytes free in ROM0 for --unbanked files (default read from --link-map)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("strategy")
+        .long("strategy")
+        .value_name("STRATEGY")
+        .help("Sets how a bank is chosen for each area, one of first-fit or best-fit (default first-fit)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("descending")
+        .long("descending")
+        .help("Fill banks from the top bank downwards, keeping low banks stable"),
+    )
+    .arg(
+      Arg::with_name("top_bank")
+        .long("top-bank")
+        .value_name("NN")
+        .help("Sets the highest bank to pack into, where --descending starts filling (default last bank of the mapper, required by --descending unless --batteryless is given)")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("explain")
+        .long("explain")
+        .value_name("FILE")
+        .help("Explain how the areas of an object file were placed")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("config")
+        .long("config")
+        .value_name("FILE")
+        .help("Read packing options from a config file (default gbspack.toml if present)")
+        .takes_value(true),
+    )
     .arg(
       Arg::with_name("output_path")
         .short("o")
@@ -55,9 +318,27 @@ fn main() -> std::io::Result<()> {
       Arg::with_name("input_file")
         .short("i")
         .long("input")
-        .help("Optionally specify a file containing .o files to pack, one file per line")
+        .help("Optionally specify a file containing .o files to pack (plain list, lcc @response file or linker -f file)")
         .takes_value(true),
     )
+    .arg(
+      Arg::with_name("include")
+        .long("include")
+        .value_name("GLOB")
+        .help("Only pack files matching pattern when an input is a directory (default *.o)")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("exclude")
+        .long("exclude")
+        .value_name("GLOB")
+        .help("Skip files matching pattern when expanding directory and glob inputs")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
     .arg(
       Arg::with_name("report_file")
         .short("r")
@@ -80,7 +361,7 @@ fn main() -> std::io::Result<()> {
     )
     .arg(
       Arg::with_name("INPUT")
-        .help("Sets the input .o files to use")
+        .help("Sets the input .o files, directories or quoted glob patterns to use, @file reads a response file")
         .multiple(true)
         .index(1),
     )
@@ -101,49 +382,199 @@ fn main() -> std::io::Result<()> {
         .short("v")
         .help("Sets the level of verbosity"),
     )
-    .get_matches();
+}
+
+fn pack(matches: &ArgMatches) -> std::io::Result<()> {
+  let config_file = matches.value_of("config");
+  let config = match gbspacklib::config::load_config(config_file) {
+    Ok(config) => config,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
 
   let verbose = matches.is_present("verbose");
-  let print_max = matches.is_present("print_max");
-  let print_cart = matches.is_present("print_cart");
-  let mbc1 = matches.is_present("mbc1");
-  let bank_offset = value_t!(matches.value_of("offset"), u32).unwrap_or(1);
-  let mut input_files = values_t!(matches.values_of("INPUT"), String).unwrap_or(Vec::new());
-  let input_file = value_t!(matches.value_of("input_file"), String).unwrap_or(("").to_string());
-  let report_file = value_t!(matches.value_of("report_file"), String).unwrap_or(("").to_string());
-  let report_head = value_t!(matches.value_of("report_head"), String).unwrap_or(("").to_string());
-  let output_path = value_t!(matches.value_of("output_path"), String).unwrap_or(("").to_string());
-  let ext = value_t!(matches.value_of("ext"), String).unwrap_or(("o").to_string());
-  let filter = value_t!(matches.value_of("filter"), u32).unwrap_or(0);
-  let additional = value_t!(matches.value_of("additional"), u32).unwrap_or(0);
-  let reserve_space = value_t!(matches.value_of("reserve_space"), String).unwrap_or(("").to_string());
-
-  let mut reserve = vec![0; 2048];
-  let reserve_split = reserve_space.split(",");
-  for s in reserve_split {
-    let split = s.split(":").collect::<Vec<&str>>();
-    if split.len() == 2 {
-      let bank = split[0].parse::<usize>().unwrap_or(0);
-      let size = u32::from_str_radix(split[1], 16).unwrap_or(0);
-      reserve[bank] = size;
-    }
-  }
-
-  if input_file.len() > 0 {
-    let lines = gbspacklib::lines_from_file(&input_file);
-    input_files = lines;
+  let print_max = matches.is_present("print_max") || config.report.print_max;
+  let print_cart = matches.is_present("print_cart") || config.report.print_cart;
+  let mapper = match matches.value_of("mapper") {
+    Some(name) => match name.parse::<gbspacklib::Mapper>() {
+      Ok(mapper) => Some(mapper),
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => config.mapper,
+  };
+  let mbc1 = matches.is_present("mbc1") || mapper == Some(gbspacklib::Mapper::Mbc1);
+  let cart_mapper = if mbc1 { gbspacklib::Mapper::Mbc1 } else { mapper.unwrap_or(gbspacklib::Mapper::Mbc5) };
+  let strategy = match matches.value_of("strategy") {
+    Some(name) => match name.parse::<gbspacklib::options::Strategy>() {
+      Ok(strategy) => strategy,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => config.strategy.unwrap_or(gbspacklib::options::Strategy::FirstFit),
+  };
+  let bank_offset = value_t!(matches.value_of("offset"), u32).ok().or(config.bank).unwrap_or(1);
+  let descending = matches.is_present("descending") || config.descending;
+  let top_bank = value_t!(matches.value_of("top_bank"), u32).ok().or(config.top_bank);
+  let input_files = values_t!(matches.values_of("INPUT"), String).unwrap_or(config.inputs);
+  let include = values_t!(matches.values_of("include"), String).unwrap_or(config.include);
+  let exclude = values_t!(matches.values_of("exclude"), String).unwrap_or(config.exclude);
+  let input_file = value_t!(matches.value_of("input_file"), String).ok().or(config.input).unwrap_or_default();
+  let report_file = value_t!(matches.value_of("report_file"), String).ok().or(config.report.file).unwrap_or_default();
+  let report_head = value_t!(matches.value_of("report_head"), String).ok().or(config.report.head).unwrap_or_default();
+  let output_path = value_t!(matches.value_of("output_path"), String).ok().or(config.output).unwrap_or_default();
+  let ext = value_t!(matches.value_of("ext"), String).ok().or(config.ext).unwrap_or_else(|| "o".to_string());
+  let filter = parse_bank_set(matches.value_of("filter"), config.filter);
+  let allow_banks = parse_bank_set(matches.value_of("allow_banks"), config.allow_banks);
+  let deny_banks = parse_bank_set(matches.value_of("deny_banks"), config.deny_banks);
+  // Bank 0 is never banked so a filter of 0 keeps the default of repacking every bank
+  let filter: Vec<u32> = filter
+    .banks()
+    .into_iter()
+    .filter(|&bank| bank != 0)
+    .collect();
+  let additional = value_t!(matches.value_of("additional"), u32).ok().or(config.additional).unwrap_or(0);
+  let save_size = match matches.value_of("batteryless").map(String::from).or(config.batteryless.size) {
+    Some(size) => match gbspacklib::save::parse_byte_size(&size) {
+      Ok(size) => Some(size),
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => None,
+  };
+  let sector_size = match matches.value_of("flash_sector").map(String::from).or(config.batteryless.sector) {
+    Some(size) => match gbspacklib::save::parse_byte_size(&size) {
+      Ok(size) => size,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => gbspacklib::save::DEFAULT_SECTOR_SIZE,
+  };
+  let reserve_specs = values_t!(matches.values_of("reserve_space"), String).unwrap_or(config.reserve);
+
+  let reserve = match gbspacklib::reserve::Reserve::parse(&reserve_specs) {
+    Ok(reserve) => reserve,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
+
+  let linker_args_file = value_t!(matches.value_of("linker_args"), String).ok().or(config.linker.file);
+  let ram_banks = value_t!(matches.value_of("ram_banks"), u32).ok().or(config.linker.ram_banks).unwrap_or(0);
+  let battery = matches.is_present("battery") || config.linker.battery;
+  let base_addresses = matches.is_present("base_addresses") || config.linker.base_addresses;
+  let header_file = value_t!(matches.value_of("emit_header"), String).ok().or(config.header);
+  let asm_file = value_t!(matches.value_of("emit_asm"), String).ok().or(config.asm_include);
+  let asm_syntax = match matches.value_of("asm_syntax") {
+    Some(name) => match name.parse::<gbspacklib::emit::AsmSyntax>() {
+      Ok(syntax) => syntax,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => config.asm_syntax.unwrap_or(gbspacklib::emit::AsmSyntax::Asxxxx),
+  };
+  let lock_file = value_t!(matches.value_of("lock_file"), String).ok().or(config.lock);
+  let link_map = value_t!(matches.value_of("link_map"), String).ok().or(config.link_map);
+  let mut rom0_free = match matches.value_of("rom0_free").map(String::from).or(config.rom0_free) {
+    Some(size) => match gbspacklib::save::parse_byte_size(&size) {
+      Ok(size) => Some(size),
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => None,
+  };
+  let map_areas = match link_map.as_ref() {
+    Some(link_map) => match gbspacklib::linkmap::read_link_map(link_map) {
+      Ok(areas) => Some(areas),
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => None,
+  };
+  // The lock from the last run records where the input files were linked in the map
+  let previous_lock = match lock_file.as_ref() {
+    Some(lock_file) if link_map.is_some() && std::path::Path::new(lock_file).is_file() => {
+      match gbspacklib::lock::read_lock(lock_file) {
+        Ok(lock) => Some(lock),
+        Err(err) => {
+          println!("gbspack: {}", err);
+          std::process::exit(1);
+        }
+      }
+    }
+    _ => None,
+  };
+
+  if filter.is_empty() && !config.pins.is_empty() {
+    println!("gbspack: Pinning objects requires a filter bank to repack from");
+    std::process::exit(1);
+  }
+
+  let pools = match matches.values_of("pool") {
+    Some(specs) => match specs.map(|spec| spec.parse()).collect::<Result<Vec<gbspacklib::config::PoolConfig>, String>>() {
+      Ok(pools) => pools,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => config.pools,
+  };
+
+  let mut input_files = read_input_files(&input_file, &input_files, &include, &exclude);
+
+  // Add the inputs of each pool which weren't already given
+  let pool_inputs: Vec<Vec<String>> = pools
+    .iter()
+    .map(|pool| {
+      if pool.inputs.is_empty() {
+        vec![]
+      } else {
+        read_input_files("", &pool.inputs, &include, &exclude)
+      }
+    })
+    .collect();
+  for filename in pool_inputs.iter().flatten() {
+    if !input_files.contains(filename) {
+      input_files.push(filename.clone());
+    }
+  }
+
+  if let Err(err) = gbspacklib::check_output_filenames(&input_files, &output_path, &ext) {
+    println!("gbspack: {}", err);
+    std::process::exit(1);
   }
 
   if verbose {
     println!("Starting at bank={}", bank_offset);
     println!("Processing {} files", input_files.len());
     println!("Using extension .{}", ext);
-    if output_path.len() > 0 {
+    if !output_path.is_empty() {
       println!("Output path={}", output_path);
     }
     if mbc1 {
       println!("Using MBC1 hardware");
     }
+    if descending {
+      println!("Filling banks downwards");
+    }
   }
 
   // Convert input files to Vec<ObjectData>
@@ -156,14 +587,321 @@ fn main() -> std::io::Result<()> {
     objects.push(object);
   }
 
+  // Keep the objects as read to find which bank symbols pinning and packing moved
+  let unpinned = if header_file.is_some() || asm_file.is_some() {
+    objects.clone()
+  } else {
+    vec![]
+  };
+
+  // Move pinned objects out of the filtered bank so they stay fixed
+  for object in objects.iter_mut() {
+    let pin = config
+      .pins
+      .iter()
+      .find(|(entry, _)| gbspacklib::config::matches_object(entry, &object.filename));
+    if let Some((_, &bank)) = pin {
+      if verbose {
+        println!("Pinning file {} to bank {}", object.filename, bank);
+      }
+      for &from in filter.iter() {
+        *object = gbspacklib::pin_object(object, from, bank);
+      }
+    }
+  }
+
+  // Seed the banks with what the link map holds apart from the input files
+  let mut existing: Vec<u32> = vec![];
+  if let (Some(link_map), Some(areas)) = (link_map.as_ref(), map_areas.as_ref()) {
+    if rom0_free.is_none() {
+      let free = gbspacklib::linkmap::outside_rom0_free(areas, &objects, previous_lock.as_ref());
+      if verbose {
+        println!("ROM0 has {} bytes free in {} without the input files", free, link_map);
+      }
+      rom0_free = Some(free);
+    }
+    let usage = gbspacklib::linkmap::outside_usage(areas, &objects, previous_lock.as_ref());
+    for (&bank, &size) in usage.iter() {
+      if existing.len() <= bank as usize {
+        existing.resize(bank as usize + 1, 0);
+      }
+      existing[bank as usize] = size;
+      if verbose {
+        println!("Bank {} has {} bytes used in {} by other files", bank, size, link_map);
+      }
+    }
+  }
+
+  let groups: Vec<Vec<usize>> = config
+    .groups
+    .iter()
+    .map(|group| {
+      objects
+        .iter()
+        .enumerate()
+        .filter(|(_, object)| {
+          group
+            .iter()
+            .any(|entry| gbspacklib::config::matches_object(entry, &object.filename))
+        })
+        .map(|(i, _)| i)
+        .collect()
+    })
+    .collect();
+
+  let span_patterns = values_t!(matches.values_of("span"), String).unwrap_or(config.spanning);
+  let spanning: Vec<usize> = objects
+    .iter()
+    .enumerate()
+    .filter(|(_, object)| {
+      span_patterns
+        .iter()
+        .any(|entry| gbspacklib::config::matches_pattern(entry, &object.filename))
+    })
+    .map(|(i, _)| i)
+    .collect();
+
+  let sequence_specs: Vec<Vec<String>> = match matches.values_of("sequence") {
+    Some(specs) => specs
+      .map(|spec| {
+        spec
+          .split(',')
+          .map(|entry| entry.trim().to_string())
+          .filter(|entry| !entry.is_empty())
+          .collect()
+      })
+      .collect(),
+    None => config.sequences,
+  };
+  // Entries are matched in the order given, glob patterns in file name order
+  let sequences: Vec<Vec<usize>> = sequence_specs
+    .iter()
+    .map(|sequence| {
+      let mut indices: Vec<usize> = vec![];
+      for entry in sequence.iter() {
+        for (i, object) in objects.iter().enumerate() {
+          if gbspacklib::config::matches_pattern(entry, &object.filename) && !indices.contains(&i) {
+            indices.push(i);
+          }
+        }
+      }
+      indices
+    })
+    .collect();
+
+  let unbanked_patterns = values_t!(matches.values_of("unbanked"), String).unwrap_or(config.unbanked);
+  let unbanked: Vec<usize> = objects
+    .iter()
+    .enumerate()
+    .filter(|(_, object)| {
+      unbanked_patterns
+        .iter()
+        .any(|entry| gbspacklib::config::matches_pattern(entry, &object.filename))
+    })
+    .map(|(i, _)| i)
+    .collect();
+  if !unbanked_patterns.is_empty() && rom0_free.is_none() {
+    println!("gbspack: Moving unbanked files into ROM0 needs --rom0-free or a --link-map to read the free space from");
+    std::process::exit(1);
+  }
+
+  let mut options = gbspacklib::options::PackOptions::builder()
+    .bank_offset(bank_offset)
+    .mapper(cart_mapper)
+    .reserve(reserve)
+    .strategy(strategy)
+    .descending(descending)
+    .groups(groups)
+    .spanning(spanning)
+    .unbanked(unbanked)
+    .rom0_free(rom0_free.unwrap_or(0))
+    .filters(&filter)
+    .allow_banks(allow_banks)
+    .deny_banks(deny_banks)
+    .existing(existing);
+  if let Some(top_bank) = top_bank {
+    options = options.max_bank(top_bank);
+  }
+  for sequence in sequences.iter() {
+    options = options.sequence(sequence.clone());
+  }
+  for (pool, inputs) in pools.into_iter().zip(pool_inputs.iter()) {
+    let pool_objects: Vec<usize> = objects
+      .iter()
+      .enumerate()
+      .filter(|(_, object)| {
+        inputs.contains(&object.filename)
+          || pool
+            .objects
+            .iter()
+            .any(|entry| gbspacklib::config::matches_pattern(entry, &object.filename))
+      })
+      .map(|(i, _)| i)
+      .collect();
+    if verbose {
+      println!("Packing {} files in pool {} (banks {})", pool_objects.len(), pool.name, pool.banks);
+    }
+    options = options.pool(gbspacklib::options::Pool {
+      name: pool.name,
+      banks: pool.banks,
+      objects: pool_objects,
+      fallback: pool.fallback,
+    });
+  }
+  // The batteryless save block goes at the end of the smallest cart the banks fit in
+  let built = match save_size {
+    Some(save_size) => {
+      gbspacklib::save::reserve_save_block(&objects, &options, save_size, sector_size, additional, cart_mapper)
+        .map(|(options, block)| (options, Some(block)))
+    }
+    None => options.build().map(|options| (options, None)),
+  };
+  let (options, save_block) = match built {
+    Ok(built) => built,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
+  if let (Some(block), Some(save_size)) = (save_block.as_ref(), save_size) {
+    if verbose {
+      println!(
+        "Keeping banks {}-{} free for batteryless save of {} bytes",
+        block.first_bank, block.last_bank, save_size
+      );
+    }
+  }
+
+  if let Some(explain) = matches.value_of("explain") {
+    match objects
+      .iter()
+      .position(|object| gbspacklib::config::matches_object(explain, &object.filename))
+    {
+      Some(index) => match gbspacklib::explain_object(objects.clone(), &options, index) {
+        Ok(traces) => print_explain(&objects[index].filename, &traces, bank_offset),
+        Err(err) => {
+          println!("gbspack: {}", err);
+          std::process::exit(1);
+        }
+      },
+      None => {
+        println!("gbspack: Unable to explain \"{}\", it is not one of the input files", explain);
+        std::process::exit(1);
+      }
+    }
+  }
+
   // Pack object data into banks
-  let packed = gbspacklib::pack_object_data(objects, filter, bank_offset, mbc1, reserve);
+  let result = match gbspacklib::pack_objects(objects, &options) {
+    Ok(result) => result,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
 
-  let max_bank_no = gbspacklib::get_patch_max_bank(&packed) + additional;
+  if let Some(block) = save_block.as_ref() {
+    if let Err(err) = gbspacklib::save::check_save_block(block, &result, &options) {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  }
+
+  if verbose {
+    for sequence in sequences.iter().filter(|sequence| !sequence.is_empty()) {
+      let first = result
+        .object_placements(sequence[0])
+        .find(|placement| options.is_autobanked(placement.from));
+      if let Some(placement) = first {
+        println!("Packing sequence of {} files from bank {}", sequence.len(), placement.bank);
+      }
+    }
+  }
+
+  let filenames: Vec<String> = result.patches.iter().map(|patch| patch.filename.clone()).collect();
+  if verbose {
+    for placement in result.placements.iter().filter(|placement| placement.bank == 0) {
+      println!(
+        "Moving {} _CODE_{} ({} bytes) into ROM0 near 0x{:04X}",
+        filenames[placement.object], placement.from, placement.size, placement.offset
+      );
+    }
+  }
+  let packed = result.patches;
+
+  let max_bank_no = match save_block {
+    Some(block) => block.last_bank,
+    None => result.max_bank + additional,
+  };
+
+  let symbols = if header_file.is_some() || asm_file.is_some() {
+    match gbspacklib::emit::bank_symbols(&packed, &unpinned) {
+      Ok(symbols) => symbols,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    }
+  } else {
+    vec![]
+  };
+
+  if let Some(header_file) = header_file {
+    let header = gbspacklib::emit::to_c_header(
+      &symbols,
+      max_bank_no,
+      gbspacklib::to_cart_size(max_bank_no),
+      save_block.as_ref(),
+    );
+    if verbose {
+      println!("Writing header file {}", header_file);
+    }
+    let mut file = File::create(&header_file)?;
+    if let Err(err) = file.write_all(header.as_bytes()) {
+      println!("gbspack: Unable to write header file \"{}\": {}", header_file, err);
+      std::process::exit(1);
+    }
+  }
+
+  if let Some(asm_file) = asm_file {
+    let include = gbspacklib::emit::to_asm_include(
+      &symbols,
+      asm_syntax,
+      max_bank_no,
+      gbspacklib::to_cart_size(max_bank_no),
+      save_block.as_ref(),
+    );
+    if verbose {
+      println!("Writing assembly include file {}", asm_file);
+    }
+    let mut file = File::create(&asm_file)?;
+    if let Err(err) = file.write_all(include.as_bytes()) {
+      println!("gbspack: Unable to write assembly include file \"{}\": {}", asm_file, err);
+      std::process::exit(1);
+    }
+  }
+
+  if let Some(lock_file) = lock_file {
+    let lock = match gbspacklib::lock::PackLock::from_patches(&packed, max_bank_no) {
+      Ok(lock) => lock,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    };
+    if verbose {
+      println!("Writing lock file {}", lock_file);
+    }
+    let mut file = File::create(&lock_file)?;
+    if let Err(err) = file.write_all(lock.to_json().as_bytes()) {
+      println!("gbspack: Unable to write lock file \"{}\": {}", lock_file, err);
+      std::process::exit(1);
+    }
+  }
 
   let mut output_filenames = Vec::new();
 
-  if report_head.len() > 0 {
+  if !report_head.is_empty() {
     output_filenames.push(report_head.replace("\\n", "\n"))
   }
 
@@ -182,21 +920,66 @@ fn main() -> std::io::Result<()> {
     }
   }
 
-  if report_file.len() > 0 {
+  if !report_file.is_empty() {
     let mut file = File::create(report_file.clone())?;
     if verbose {
       println!("Writing report file {}", report_file);
     }
-    match file.write_all(output_filenames.join("\n").as_bytes()) {
+    if let Err(err) = file.write_all(output_filenames.join("\n").as_bytes()) {
+      println!("gbspack: Unable to write report file \"{}\": {}", report_file, err);
+      std::process::exit(1);
+    }
+  }
+
+  if let Some(linker_args_file) = linker_args_file {
+    let linker_args = gbspacklib::linker::LinkerArgs {
+      mapper: cart_mapper,
+      rom_banks: gbspacklib::to_cart_size(max_bank_no),
+      ram_banks,
+      battery,
+    };
+    let mut args = match linker_args.to_args() {
+      Ok(args) => args,
       Err(err) => {
-        println!("gbspack: Unable to write report file \"{}\": {}", report_file, err);
+        println!("gbspack: {}", err);
         std::process::exit(1);
-      },
-      Ok(_) => {}
+      }
+    };
+    if base_addresses {
+      args.extend(gbspacklib::linker::base_address_args(&result.banks));
+    }
+    if verbose {
+      println!("Writing linker options file {}", linker_args_file);
+    }
+    let mut file = File::create(&linker_args_file)?;
+    if let Err(err) = file.write_all(format!("{}\n", args.join("\n")).as_bytes()) {
+      println!("gbspack: Unable to write linker options file \"{}\": {}", linker_args_file, err);
+      std::process::exit(1);
     }
   }
 
   if verbose {
+    for bank in result.banks.iter() {
+      match bank.spanned_from {
+        Some(first) => println!("Bank {} is used by the area spanning from bank {}", bank.bank, first),
+        None => println!("Bank {} has {} bytes used and {} bytes free", bank.bank, bank.size(), bank.free()),
+      }
+      for area in bank.areas.iter() {
+        println!(
+          "  0x{:04X} {} _CODE_{} ({} bytes)",
+          gbspacklib::linker::BANKED_BASE + area.offset,
+          filenames[area.object],
+          area.bank,
+          area.size
+        );
+      }
+    }
+    for bank in 1..=max_bank_no {
+      let reserved = options.reserved(bank);
+      if reserved > 0 {
+        println!("Reserved {} bytes in bank {}", reserved, bank);
+      }
+    }
     println!("Done");
   }
 
@@ -208,3 +991,280 @@ fn main() -> std::io::Result<()> {
 
   Ok(())
 }
+
+/// Parse a bank list given on the command line, falling back to the config file
+fn parse_bank_set(spec: Option<&str>, config: Option<gbspacklib::banks::BankSet>) -> gbspacklib::banks::BankSet {
+  match spec {
+    Some(spec) => match spec.parse::<gbspacklib::banks::BankSet>() {
+      Ok(banks) => banks,
+      Err(err) => {
+        println!("gbspack: {}", err);
+        std::process::exit(1);
+      }
+    },
+    None => config.unwrap_or_default(),
+  }
+}
+
+/// Print the trace of how each area of an object was placed
+fn print_explain(filename: &str, traces: &[gbspacklib::explain::AreaTrace], bank_offset: u32) {
+  use gbspacklib::explain::AttemptOutcome;
+
+  println!("{}", filename);
+  for trace in traces.iter() {
+    let position = match trace.position {
+      Some(position) => position,
+      None if trace.bank == 0 => {
+        println!(
+          "  Area _CODE_{} ({} bytes) was moved into free ROM0 space as one of the smallest unbanked areas",
+          trace.from, trace.size
+        );
+        continue;
+      }
+      None => {
+        println!(
+          "  Area _CODE_{} ({} bytes) is fixed in bank {} as it is absolute or outside the filtered banks",
+          trace.from, trace.size, trace.bank
+        );
+        continue;
+      }
+    };
+    println!(
+      "  Area _CODE_{} ({} bytes) was placed {} of {} by descending size",
+      trace.from, trace.size, position, trace.count
+    );
+    let below_offset = trace
+      .attempts
+      .iter()
+      .filter(|attempt| attempt.outcome == AttemptOutcome::BelowOffset)
+      .count();
+    match below_offset {
+      0 => {}
+      1 => println!("    Bank 1 skipped, below bank offset {}", bank_offset),
+      _ => println!("    Banks 1-{} skipped, below bank offset {}", below_offset, bank_offset),
+    }
+    for attempt in trace.attempts.iter() {
+      let reason = match attempt.outcome {
+        AttemptOutcome::BelowOffset => continue,
+        AttemptOutcome::Denied => "denied",
+        AttemptOutcome::NoSpace => "no space",
+        AttemptOutcome::Reserved => "ruled out by reserved space",
+        AttemptOutcome::Fits => "fits",
+        AttemptOutcome::Placed => "placed",
+        AttemptOutcome::NewBank => "placed in new bank",
+      };
+      let reserved = if attempt.reserved > 0 {
+        format!(" ({} reserved)", attempt.reserved)
+      } else {
+        String::new()
+      };
+      println!(
+        "    Bank {}: {} bytes free{}, {} required - {}",
+        attempt.bank,
+        attempt.free(),
+        reserved,
+        attempt.required,
+        reason
+      );
+    }
+    if trace.attempts.first().map_or(false, |attempt| attempt.required != trace.size) {
+      println!("    Required size includes the other areas grouped with this object");
+    }
+    println!("  Packed into bank {}", trace.bank);
+  }
+}
+
+/// Expand command line and input file arguments into the list of object files to use
+fn read_input_files(input_file: &str, input_files: &[String], include: &[String], exclude: &[String]) -> Vec<String> {
+  let input_result = if !input_file.is_empty() {
+    gbspacklib::input::read_input_file(input_file)
+  } else {
+    gbspacklib::input::expand_input_args(input_files)
+  };
+  let input_result = input_result
+    .and_then(|files| gbspacklib::input::resolve_inputs(&files, include, exclude));
+  match input_result {
+    Ok(files) => files,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  }
+}
+
+fn verify(matches: &ArgMatches) -> std::io::Result<()> {
+  let map_file = matches.value_of("map").unwrap();
+  let input_files = values_t!(matches.values_of("INPUT"), String).unwrap_or(Vec::new());
+  let input_file = matches.value_of("input_file").unwrap_or("");
+  let input_files = read_input_files(input_file, &input_files, &[], &[]);
+
+  let areas = match gbspacklib::linkmap::read_link_map(map_file) {
+    Ok(areas) => areas,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
+
+  let objects = read_objects(input_files)?;
+
+  let mut overflow = false;
+  for check in gbspacklib::verify::verify_banks(&objects, &areas) {
+    let status = if check.is_overflow() {
+      overflow = true;
+      "OVERFLOW"
+    } else if check.drift() != 0 {
+      "DRIFT"
+    } else {
+      "OK"
+    };
+    println!(
+      "Bank {}: planned {} bytes, linked {} bytes, drift {:+} bytes {}",
+      check.bank,
+      check.planned,
+      check.actual,
+      check.drift(),
+      status
+    );
+  }
+
+  if overflow {
+    println!("gbspack: Bank overflow in linked ROM");
+    std::process::exit(1);
+  }
+
+  Ok(())
+}
+
+fn read_objects(input_files: Vec<String>) -> std::io::Result<Vec<gbspacklib::ObjectData>> {
+  let mut objects = Vec::new();
+  for filename in input_files {
+    objects.push(gbspacklib::to_object_data(&filename)?);
+  }
+  Ok(objects)
+}
+
+fn info(matches: &ArgMatches) -> std::io::Result<()> {
+  let input_files = values_t!(matches.values_of("INPUT"), String).unwrap_or(Vec::new());
+  let input_files = read_input_files("", &input_files, &[], &[]);
+
+  for object in read_objects(input_files)? {
+    let info = gbspacklib::inspect::object_info(&object);
+    println!("{}", info.filename);
+    println!("  Version: {}", info.version);
+    println!("  Header: {}", info.header);
+    println!("  Areas:");
+    for area in info.areas.iter() {
+      let bank = match area.bank {
+        Some(bank) => format!("bank {}", bank),
+        None => "unbanked".to_string(),
+      };
+      println!(
+        "    {} {} size {} bytes (0x{:X}) flags 0x{:X} addr 0x{:X}",
+        area.name, bank, area.size, area.size, area.flags, area.addr
+      );
+    }
+    println!("  Symbols:");
+    for symbol in info.symbols.iter() {
+      let kind = if symbol.def { "Def" } else { "Ref" };
+      println!("    {} {} 0x{:06X}", symbol.name, kind, symbol.value);
+    }
+    for (bank, symbols) in info.rewrites.iter() {
+      if symbols.is_empty() {
+        println!("  Moving bank {} rewrites no symbols", bank);
+      } else {
+        println!("  Moving bank {} rewrites: {}", bank, symbols.join(", "));
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn map(matches: &ArgMatches) -> std::io::Result<()> {
+  let input_files = values_t!(matches.values_of("INPUT"), String).unwrap_or(Vec::new());
+  let input_file = matches.value_of("input_file").unwrap_or("");
+  let input_files = read_input_files(input_file, &input_files, &[], &[]);
+
+  for bank in gbspacklib::inspect::bank_usage(&read_objects(input_files)?) {
+    println!("Bank {}: {} bytes used, {} bytes free", bank.bank, bank.size(), bank.free());
+    for (filename, size) in bank.areas.iter() {
+      println!("  {} {} bytes", filename, size);
+    }
+  }
+
+  Ok(())
+}
+
+fn diff(matches: &ArgMatches) -> std::io::Result<()> {
+  let old = read_pack_lock(matches.value_of("OLD").unwrap())?;
+  let new = read_pack_lock(matches.value_of("NEW").unwrap())?;
+  let diff = match gbspacklib::lock::diff_locks(&old, &new) {
+    Ok(diff) => diff,
+    Err(err) => {
+      println!("gbspack: {}", err);
+      std::process::exit(1);
+    }
+  };
+
+  println!("Max bank {} -> {}", diff.old_max_bank, diff.new_max_bank);
+  for change in diff.areas.iter() {
+    match (change.old, change.new) {
+      (Some(old), Some(new)) if old.bank != new.bank => println!(
+        "~ {} area {} moved from bank {} to {} ({} -> {} bytes)",
+        change.name, change.index, old.bank, new.bank, old.size, new.size
+      ),
+      (Some(old), Some(new)) => println!(
+        "~ {} area {} in bank {} grew by {} bytes ({} -> {} bytes)",
+        change.name,
+        change.index,
+        new.bank,
+        change.growth(),
+        old.size,
+        new.size
+      ),
+      (None, Some(new)) => println!("+ {} area {} added in bank {} ({} bytes)", change.name, change.index, new.bank, new.size),
+      (Some(old), None) => println!("- {} area {} removed from bank {} ({} bytes)", change.name, change.index, old.bank, old.size),
+      (None, None) => {}
+    }
+  }
+  for bank in diff.banks.iter() {
+    println!(
+      "Bank {}: {} -> {} bytes ({:+})",
+      bank.bank,
+      bank.old_size,
+      bank.new_size,
+      bank.new_size as i64 - bank.old_size as i64
+    );
+  }
+  for (bank, triggers) in diff.new_banks.iter() {
+    if triggers.is_empty() {
+      println!("Bank {} was added", bank);
+    } else {
+      println!("Bank {} was added for {}", bank, triggers.join(", "));
+    }
+  }
+
+  Ok(())
+}
+
+/// Load a packing result from a lock file, or from a directory,
+/// glob pattern or @file of packed object files
+fn read_pack_lock(path: &str) -> std::io::Result<gbspacklib::lock::PackLock> {
+  // Lock files are JSON objects, anything else is read as packed object files
+  // whatever its extension
+  if std::path::Path::new(path).is_file() {
+    let contents = std::fs::read(path)?;
+    if contents.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{') {
+      return match gbspacklib::lock::read_lock(path) {
+        Ok(lock) => Ok(lock),
+        Err(err) => {
+          println!("gbspack: {}", err);
+          std::process::exit(1);
+        }
+      };
+    }
+  }
+  let files = read_input_files("", &[path.to_string()], &[], &[]);
+  Ok(gbspacklib::lock::PackLock::from_objects(&read_objects(files)?))
+}
@@ -0,0 +1,513 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+use crate::banks::BankSet;
+use crate::reserve::{Reserve, ReserveAmount, ReserveBanks, ReserveRule};
+use crate::save::SaveBlock;
+use crate::{Mapper, ObjectBankData, BANK_SIZE};
+
+/// How the packer picks a bank for each area
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Use the lowest bank with enough free space
+    FirstFit,
+    /// Use the bank which will have the least free space left
+    BestFit,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "first-fit" => Ok(Strategy::FirstFit),
+            "best-fit" => Ok(Strategy::BestFit),
+            _ => Err(format!("Unknown strategy \"{}\", expected first-fit or best-fit", name)),
+        }
+    }
+}
+
+/// Name of the pool holding every object which isn't in a declared pool
+pub const DEFAULT_POOL: &str = "default";
+
+/// A named set of objects packed into their own range of banks
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pool {
+    pub name: String,
+    pub banks: BankSet,
+    /// Indices of the objects in the pool
+    pub objects: Vec<usize>,
+    /// Pool to place areas in when this pool is full, `"default"`
+    /// for the banks outside every declared pool
+    pub fallback: Option<String>,
+}
+
+/// Validated options for `pack_objects`, created with `PackOptions::builder()`
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    pub(crate) bank_offset: u32,
+    pub(crate) filter: Vec<u32>,
+    pub(crate) mapper: Mapper,
+    pub(crate) reserve: Reserve,
+    pub(crate) strategy: Strategy,
+    pub(crate) descending: bool,
+    pub(crate) bank_size: u32,
+    pub(crate) max_bank: u32,
+    pub(crate) allow: BankSet,
+    pub(crate) deny: BankSet,
+    pub(crate) groups: Vec<Vec<usize>>,
+    pub(crate) spanning: Vec<usize>,
+    pub(crate) sequences: Vec<Vec<usize>>,
+    pub(crate) unbanked: Vec<usize>,
+    pub(crate) rom0_free: u32,
+    pub(crate) existing: Vec<u32>,
+    pub(crate) pools: Vec<Pool>,
+    /// Index of the fallback pool for each pool, where the default
+    /// pool has the index `pools.len()`
+    pub(crate) fallbacks: Vec<Option<usize>>,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        PackOptions::builder().build().unwrap()
+    }
+}
+
+impl PackOptions {
+    pub fn builder() -> PackOptionsBuilder {
+        PackOptionsBuilder::default()
+    }
+
+    pub fn bank_offset(&self) -> u32 {
+        self.bank_offset
+    }
+
+    /// Banks whose areas are repacked, empty when every area is repacked
+    pub fn filter(&self) -> &[u32] {
+        &self.filter
+    }
+
+    pub fn mapper(&self) -> Mapper {
+        self.mapper
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Whether areas are packed from the max bank downwards
+    pub fn descending(&self) -> bool {
+        self.descending
+    }
+
+    pub fn bank_size(&self) -> u32 {
+        self.bank_size
+    }
+
+    pub fn max_bank(&self) -> u32 {
+        self.max_bank
+    }
+
+    /// Check if an area in a bank of an input object file should be repacked
+    pub fn is_autobanked(&self, bank: u32) -> bool {
+        self.filter.is_empty() || self.filter.contains(&bank)
+    }
+
+    /// Check if an area must stay in its bank, either because it's outside
+    /// the filtered banks or because it's absolute
+    pub fn is_fixed(&self, area: &ObjectBankData) -> bool {
+        !self.is_autobanked(area.bank) || area.is_absolute()
+    }
+
+    /// Check if areas can be packed into a bank. Banks must be in the allowed
+    /// banks if any are given and not denied.
+    pub fn is_allowed(&self, bank: u32) -> bool {
+        (self.allow.is_empty() || self.allow.contains(bank)) && !self.is_denied(bank)
+    }
+
+    /// Check if a bank is in the denied banks. With MBC1 banks 0x20, 0x40
+    /// and 0x60 are always denied as they can't be switched in. Banks outside
+    /// the allowed banks aren't denied, so may still hold fixed areas.
+    pub fn is_denied(&self, bank: u32) -> bool {
+        if self.mapper == Mapper::Mbc1 && (bank == 0x20 || bank == 0x40 || bank == 0x60) {
+            return true;
+        }
+        self.deny.contains(bank)
+    }
+
+    /// Check if an object's areas may be larger than a bank and
+    /// span a run of consecutive banks
+    pub fn is_spanning(&self, object: usize) -> bool {
+        self.spanning.contains(&object)
+    }
+
+    /// Lists of object indices packed into consecutive banks in order
+    pub fn sequences(&self) -> &[Vec<usize>] {
+        &self.sequences
+    }
+
+    /// Check if an object's areas can be read without switching banks,
+    /// so may be moved into free space in ROM0
+    pub fn is_unbanked(&self, object: usize) -> bool {
+        self.unbanked.contains(&object)
+    }
+
+    /// Bytes free in ROM0 for areas of unbanked objects
+    pub fn rom0_free(&self) -> u32 {
+        self.rom0_free
+    }
+
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+
+    /// Index of the pool an object is packed in, or `pools().len()`
+    /// for objects in the default pool
+    pub fn pool_of(&self, object: usize) -> usize {
+        self.pools
+            .iter()
+            .position(|pool| pool.objects.contains(&object))
+            .unwrap_or(self.pools.len())
+    }
+
+    /// Name of a pool by index
+    pub fn pool_name(&self, pool: usize) -> &str {
+        self.pools.get(pool).map(|pool| pool.name.as_str()).unwrap_or(DEFAULT_POOL)
+    }
+
+    /// Check if a pool can use a bank, the default pool
+    /// uses every bank outside the declared pools
+    pub fn pool_contains(&self, pool: usize, bank: u32) -> bool {
+        match self.pools.get(pool) {
+            Some(pool) => pool.banks.contains(bank),
+            None => !self.pools.iter().any(|pool| pool.banks.contains(bank)),
+        }
+    }
+
+    /// Pool to use when a pool is full
+    pub fn fallback(&self, pool: usize) -> Option<usize> {
+        self.fallbacks.get(pool).copied().flatten()
+    }
+
+    /// Number of bytes to keep free in a bank
+    pub fn reserved(&self, bank: u32) -> u32 {
+        self.reserve.for_bank_size(bank, self.bank_size).min(self.bank_size)
+    }
+
+    /// Number of bytes used in a bank by contents gbspack doesn't pack
+    pub fn existing(&self, bank: u32) -> u32 {
+        self.existing.get(bank as usize).copied().unwrap_or(0)
+    }
+}
+
+/// Builder for `PackOptions`. By default every area is repacked into
+/// MBC5 banks starting at bank 1 using first fit with no reserved space.
+#[derive(Debug, Clone)]
+pub struct PackOptionsBuilder {
+    bank_offset: u32,
+    filter: Vec<u32>,
+    mapper: Mapper,
+    reserve: Reserve,
+    strategy: Strategy,
+    descending: bool,
+    bank_size: u32,
+    max_bank: Option<u32>,
+    allow: BankSet,
+    deny: BankSet,
+    groups: Vec<Vec<usize>>,
+    spanning: Vec<usize>,
+    sequences: Vec<Vec<usize>>,
+    unbanked: Vec<usize>,
+    rom0_free: u32,
+    existing: Vec<u32>,
+    pools: Vec<Pool>,
+}
+
+impl Default for PackOptionsBuilder {
+    fn default() -> Self {
+        PackOptionsBuilder {
+            bank_offset: 1,
+            filter: vec![],
+            mapper: Mapper::Mbc5,
+            reserve: Reserve::default(),
+            strategy: Strategy::FirstFit,
+            descending: false,
+            bank_size: BANK_SIZE,
+            max_bank: None,
+            allow: BankSet::default(),
+            deny: BankSet::default(),
+            groups: vec![],
+            spanning: vec![],
+            sequences: vec![],
+            unbanked: vec![],
+            rom0_free: 0,
+            existing: vec![],
+            pools: vec![],
+        }
+    }
+}
+
+impl PackOptionsBuilder {
+    /// First bank to pack areas into
+    pub fn bank_offset(mut self, bank_offset: u32) -> Self {
+        self.bank_offset = bank_offset;
+        self
+    }
+
+    /// Only repack areas from this bank, may be called more than once
+    pub fn filter(mut self, bank: u32) -> Self {
+        self.filter.push(bank);
+        self
+    }
+
+    /// Only repack areas from these banks
+    pub fn filters(mut self, banks: &[u32]) -> Self {
+        self.filter.extend_from_slice(banks);
+        self
+    }
+
+    pub fn mapper(mut self, mapper: Mapper) -> Self {
+        self.mapper = mapper;
+        self
+    }
+
+    pub fn reserve(mut self, reserve: Reserve) -> Self {
+        self.reserve = reserve;
+        self
+    }
+
+    /// Reserve a number of bytes in each bank from a list indexed by bank number
+    pub fn reserve_sizes(mut self, sizes: &[u32]) -> Self {
+        self.reserve.rules.extend(
+            sizes
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter(|(_, &size)| size > 0)
+                .map(|(bank, &size)| ReserveRule {
+                    banks: ReserveBanks::Range(bank as u32, bank as u32),
+                    amount: ReserveAmount::Bytes(size),
+                }),
+        );
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Fill banks starting from the max bank and moving down towards
+    /// the bank offset, rather than upwards from the bank offset. The max
+    /// bank must be given, as the mapper's last bank would need the largest
+    /// cart it supports.
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Number of bytes which can be packed into each bank
+    pub fn bank_size(mut self, bank_size: u32) -> Self {
+        self.bank_size = bank_size;
+        self
+    }
+
+    /// Highest bank to pack into, defaults to the last bank the mapper supports
+    pub fn max_bank(mut self, max_bank: u32) -> Self {
+        self.max_bank = Some(max_bank);
+        self
+    }
+
+    /// Only pack autobanked areas into these banks
+    pub fn allow_banks(mut self, banks: BankSet) -> Self {
+        self.allow = banks;
+        self
+    }
+
+    /// Never pack autobanked areas into these banks
+    pub fn deny_banks(mut self, banks: BankSet) -> Self {
+        self.deny = banks;
+        self
+    }
+
+    /// Keep the banks of a batteryless save block free, along with `additional`
+    /// banks ahead of it, and pack no further than the end of its cart
+    pub fn save_block(mut self, block: &SaveBlock, additional: u32) -> Self {
+        let first = block.first_bank.saturating_sub(additional).max(1);
+        self.deny.ranges.push((first, block.last_bank));
+        self.max_bank = Some(self.max_bank.map_or(block.last_bank, |max_bank| max_bank.min(block.last_bank)));
+        self
+    }
+
+    /// Groups of object indices whose repacked areas must share a bank
+    pub fn groups(mut self, groups: Vec<Vec<usize>>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Object indices of data-only objects whose areas may be larger than a
+    /// bank, each taking a run of consecutive banks of its own
+    pub fn spanning(mut self, objects: Vec<usize>) -> Self {
+        self.spanning = objects;
+        self
+    }
+
+    /// Pack each object in the list into the bank after the previous one, so
+    /// only the first bank is needed to find the rest. May be called more than once
+    pub fn sequence(mut self, objects: Vec<usize>) -> Self {
+        self.sequences.push(objects);
+        self
+    }
+
+    /// Object indices of objects the engine can read without switching banks,
+    /// whose smallest areas are moved into bank 0 while they fit in `rom0_free`
+    pub fn unbanked(mut self, objects: Vec<usize>) -> Self {
+        self.unbanked = objects;
+        self
+    }
+
+    /// Bytes free at the end of ROM0 which unbanked areas can be moved into
+    pub fn rom0_free(mut self, rom0_free: u32) -> Self {
+        self.rom0_free = rom0_free;
+        self
+    }
+
+    /// Bytes already used in each bank by contents gbspack doesn't pack, such as
+    /// library code from a previous link, indexed by bank number
+    pub fn existing(mut self, existing: Vec<u32>) -> Self {
+        self.existing = existing;
+        self
+    }
+
+    /// Pack a set of objects into their own range of banks, may be called more than once
+    pub fn pool(mut self, pool: Pool) -> Self {
+        self.pools.push(pool);
+        self
+    }
+
+    pub fn build(self) -> Result<PackOptions, String> {
+        let mapper_max_bank = self.mapper.max_rom_banks() - 1;
+        let max_bank = self.max_bank.unwrap_or(mapper_max_bank);
+
+        if self.bank_size == 0 || self.bank_size > BANK_SIZE {
+            return Err(format!(
+                "Bank size {} is out of range (1-{})",
+                self.bank_size, BANK_SIZE
+            ));
+        }
+        if max_bank == 0 || max_bank > mapper_max_bank {
+            return Err(format!(
+                "Max bank {} is out of range, {:?} supports banks 1-{}",
+                max_bank, self.mapper, mapper_max_bank
+            ));
+        }
+        if self.descending && self.max_bank.is_none() {
+            return Err("Filling banks downwards needs a top bank to start from".to_string());
+        }
+        if self.bank_offset == 0 || self.bank_offset > max_bank {
+            return Err(format!(
+                "Bank offset {} is out of range (1-{})",
+                self.bank_offset, max_bank
+            ));
+        }
+        if self.filter.contains(&0) {
+            return Err("Filter bank 0 is not banked and can't be repacked".to_string());
+        }
+        for rule in self.reserve.rules.iter() {
+            if let ReserveAmount::Bytes(size) = rule.amount {
+                if size > BANK_SIZE {
+                    return Err(format!(
+                        "Reserve of {} bytes is larger than a bank ({} bytes)",
+                        size, BANK_SIZE
+                    ));
+                }
+            }
+        }
+
+        if self.rom0_free > BANK_SIZE {
+            return Err(format!(
+                "ROM0 free space of {} bytes is larger than ROM0 ({} bytes)",
+                self.rom0_free, BANK_SIZE
+            ));
+        }
+
+        let fallbacks = pool_fallbacks(&self.pools)?;
+
+        let mut filter = self.filter;
+        filter.sort_unstable();
+        filter.dedup();
+
+        let options = PackOptions {
+            bank_offset: self.bank_offset,
+            filter,
+            mapper: self.mapper,
+            reserve: self.reserve,
+            strategy: self.strategy,
+            descending: self.descending,
+            bank_size: self.bank_size,
+            max_bank,
+            allow: self.allow,
+            deny: self.deny,
+            groups: self.groups,
+            spanning: self.spanning,
+            sequences: self.sequences.into_iter().filter(|sequence| !sequence.is_empty()).collect(),
+            unbanked: self.unbanked,
+            rom0_free: self.rom0_free,
+            existing: self.existing,
+            pools: self.pools,
+            fallbacks,
+        };
+
+        if !(options.bank_offset..=max_bank).any(|bank| options.is_allowed(bank)) {
+            return Err(format!(
+                "No banks between bank offset {} and max bank {} are allowed",
+                options.bank_offset, max_bank
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// Check pool names are unique and resolve each pool's fallback to an index
+fn pool_fallbacks(pools: &[Pool]) -> Result<Vec<Option<usize>>, String> {
+    let index_of = |name: &str| -> Result<usize, String> {
+        if name == DEFAULT_POOL {
+            return Ok(pools.len());
+        }
+        pools
+            .iter()
+            .position(|pool| pool.name == name)
+            .ok_or_else(|| format!("Unknown fallback pool \"{}\"", name))
+    };
+
+    for (index, pool) in pools.iter().enumerate() {
+        if pool.name == DEFAULT_POOL || pools[..index].iter().any(|other| other.name == pool.name) {
+            return Err(format!("Pool name \"{}\" is already used", pool.name));
+        }
+        if pool.banks.is_empty() {
+            return Err(format!("Pool \"{}\" has no banks", pool.name));
+        }
+    }
+
+    let fallbacks = pools
+        .iter()
+        .map(|pool| pool.fallback.as_deref().map(index_of).transpose())
+        .collect::<Result<Vec<Option<usize>>, String>>()?;
+
+    // Follow each chain of fallbacks to make sure they don't loop
+    for (index, pool) in pools.iter().enumerate() {
+        let mut next = fallbacks[index];
+        for _ in 0..pools.len() {
+            next = match next {
+                Some(next) => fallbacks.get(next).copied().flatten(),
+                None => break,
+            };
+        }
+        if next.is_some() {
+            return Err(format!("Fallback pools of \"{}\" form a loop", pool.name));
+        }
+    }
+
+    Ok(fallbacks)
+}
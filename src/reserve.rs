@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use crate::banks::parse_range;
+use crate::BANK_SIZE;
+
+/// Highest bank number addressable by any supported mapper
+pub const MAX_BANK: u32 = 511;
+
+/// Number of entries in the per bank reserve list passed to the packer
+const RESERVE_BANKS: usize = 2048;
+
+/// Banks a reserve rule applies to
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReserveBanks {
+    All,
+    Range(u32, u32),
+}
+
+/// Amount of space a reserve rule keeps free in each bank
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReserveAmount {
+    Bytes(u32),
+    Percent(u32),
+    Full,
+}
+
+/// A single reserve rule in the format `banks:amount` where banks is a bank
+/// number, a range such as `1-8` or `all`, and amount is a hex byte count,
+/// a percentage of the bank such as `10%` or `full` to reserve the whole bank
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReserveRule {
+    pub banks: ReserveBanks,
+    pub amount: ReserveAmount,
+}
+
+impl ReserveRule {
+    pub fn applies_to(&self, bank: u32) -> bool {
+        match self.banks {
+            ReserveBanks::All => true,
+            ReserveBanks::Range(from, to) => (from..=to).contains(&bank),
+        }
+    }
+
+    /// Number of bytes reserved in each bank the rule applies to
+    pub fn size(&self) -> u32 {
+        self.size_in(BANK_SIZE)
+    }
+
+    /// Number of bytes reserved in each bank the rule applies to when banks
+    /// hold `bank_size` bytes, which percentages and full banks are taken from
+    pub fn size_in(&self, bank_size: u32) -> u32 {
+        match self.amount {
+            ReserveAmount::Bytes(size) => size,
+            ReserveAmount::Percent(percent) => bank_size * percent / 100,
+            ReserveAmount::Full => bank_size,
+        }
+    }
+}
+
+impl FromStr for ReserveRule {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let split = spec.trim().split(':').collect::<Vec<&str>>();
+        if split.len() != 2 {
+            return Err(format!(
+                "Invalid reserve \"{}\", expected format bank:size e.g. 1:7F3, 1-8:200, all:10% or 5:full",
+                spec
+            ));
+        }
+        let banks = parse_banks(split[0].trim()).map_err(|err| format!("Invalid reserve \"{}\": {}", spec, err))?;
+        let amount = parse_amount(split[1].trim()).map_err(|err| format!("Invalid reserve \"{}\": {}", spec, err))?;
+        Ok(ReserveRule { banks, amount })
+    }
+}
+
+fn parse_banks(banks: &str) -> Result<ReserveBanks, String> {
+    if banks == "all" || banks == "*" {
+        return Ok(ReserveBanks::All);
+    }
+    // Bank 0 is never packed into so can't be reserved
+    let (from, to) = parse_range(banks, 1)?;
+    Ok(ReserveBanks::Range(from, to))
+}
+
+fn parse_amount(amount: &str) -> Result<ReserveAmount, String> {
+    if amount == "full" {
+        return Ok(ReserveAmount::Full);
+    }
+    if let Some(percent) = amount.strip_suffix('%') {
+        let percent = percent
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("\"{}\" is not a percentage", amount))?;
+        if percent > 100 {
+            return Err(format!("{}% is more than a whole bank", percent));
+        }
+        return Ok(ReserveAmount::Percent(percent));
+    }
+    let hex = amount.trim_start_matches("0x").trim_start_matches("0X");
+    let size = u32::from_str_radix(hex, 16).map_err(|_| format!("\"{}\" is not a hex size", amount))?;
+    if size > BANK_SIZE {
+        return Err(format!("size {:X} is larger than a bank ({:X})", size, BANK_SIZE));
+    }
+    Ok(ReserveAmount::Bytes(size))
+}
+
+/// Space to keep free in each bank, built from a list of reserve rules.
+/// Where several rules apply to a bank the largest reservation is used.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Reserve {
+    pub rules: Vec<ReserveRule>,
+}
+
+impl Reserve {
+    /// Parse reserve specs, each of which may hold several comma separated rules
+    pub fn parse(specs: &[String]) -> Result<Reserve, String> {
+        let rules = specs
+            .iter()
+            .flat_map(|spec| spec.split(','))
+            .filter(|spec| !spec.trim().is_empty())
+            .map(|spec| spec.parse::<ReserveRule>())
+            .collect::<Result<Vec<ReserveRule>, String>>()?;
+        Ok(Reserve { rules })
+    }
+
+    /// Number of bytes reserved in a bank
+    pub fn for_bank(&self, bank: u32) -> u32 {
+        self.for_bank_size(bank, BANK_SIZE)
+    }
+
+    /// Number of bytes reserved in a bank holding `bank_size` bytes
+    pub fn for_bank_size(&self, bank: u32, bank_size: u32) -> u32 {
+        self.rules
+            .iter()
+            .filter(|rule| rule.applies_to(bank))
+            .map(|rule| rule.size_in(bank_size))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Convert to the per bank list used by `pack_object_data`
+    pub fn to_vec(&self) -> Vec<u32> {
+        (0..RESERVE_BANKS as u32)
+            .map(|bank| if bank == 0 { 0 } else { self.for_bank(bank) })
+            .collect()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::options::{PackOptions, PackOptionsBuilder};
+use crate::{pack_objects, Mapper, ObjectData, PackResult, BANK_SIZE};
+
+/// Default flash sector size erased by batteryless save routines
+pub const DEFAULT_SECTOR_SIZE: u32 = 0x10000;
+
+/// Block of banks at the end of the cart kept free for a batteryless save
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SaveBlock {
+    pub first_bank: u32,
+    pub last_bank: u32,
+    /// Number of banks in the cart including the save block
+    pub cart_size: u32,
+}
+
+impl SaveBlock {
+    pub fn banks(&self) -> u32 {
+        self.last_bank - self.first_bank + 1
+    }
+
+    pub fn contains(&self, bank: u32) -> bool {
+        (self.first_bank..=self.last_bank).contains(&bank)
+    }
+}
+
+/// Parse a byte size such as `32768`, `0x8000`, `32K` or `32KB`
+pub fn parse_byte_size(size: &str) -> Result<u32, String> {
+    let trimmed = size.trim();
+    let upper = trimmed.to_uppercase();
+    let parsed = if let Some(kilobytes) = upper.strip_suffix("KB").or_else(|| upper.strip_suffix('K')) {
+        kilobytes.trim().parse::<u32>().ok().and_then(|kilobytes| kilobytes.checked_mul(1024))
+    } else if let Some(hex) = upper.strip_prefix("0X") {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        upper.parse::<u32>().ok()
+    };
+    match parsed {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(format!("\"{}\" is not a valid size, expected e.g. 32768, 0x8000 or 32K", size)),
+    }
+}
+
+/// Find the block of banks large enough for `save_size` bytes at the end of a
+/// cart of `cart_size` banks, starting on a flash sector boundary, or `None`
+/// if the block would take in bank 0
+pub fn save_block_for_cart(cart_size: u32, save_size: u32, sector_size: u32) -> Result<Option<SaveBlock>, String> {
+    if sector_size == 0 || !(sector_size % BANK_SIZE == 0 || BANK_SIZE % sector_size == 0) {
+        return Err(format!(
+            "Flash sector size {:#X} must be a multiple or a divisor of the bank size ({:#X})",
+            sector_size, BANK_SIZE
+        ));
+    }
+    let sector_banks = (sector_size / BANK_SIZE).max(1);
+    let sectors = (save_size + sector_size - 1) / sector_size;
+    let block_banks = (sectors * sector_size + BANK_SIZE - 1) / BANK_SIZE;
+    if cart_size < block_banks {
+        return Ok(None);
+    }
+    let first_bank = (cart_size - block_banks) / sector_banks * sector_banks;
+    if first_bank == 0 {
+        return Ok(None);
+    }
+    Ok(Some(SaveBlock {
+        first_bank,
+        last_bank: first_bank + block_banks - 1,
+        cart_size,
+    }))
+}
+
+/// Find the smallest cart the objects can be packed into with a save block
+/// of `save_size` bytes at its end, returning the options which keep the
+/// block and `additional` banks ahead of it free. The block is known before
+/// packing so descending packing, the max bank and pools all work around it.
+pub fn reserve_save_block(
+    objects: &[ObjectData],
+    options: &PackOptionsBuilder,
+    save_size: u32,
+    sector_size: u32,
+    additional: u32,
+    mapper: Mapper,
+) -> Result<(PackOptions, SaveBlock), String> {
+    let mut error = None;
+    let mut cart_size = 2;
+    while cart_size <= mapper.max_rom_banks() {
+        if let Some(block) = save_block_for_cart(cart_size, save_size, sector_size)? {
+            let packed = options.clone().save_block(&block, additional).build().and_then(|options| {
+                let result = pack_objects(objects.to_vec(), &options)?;
+                check_save_block(&block, &result, &options)?;
+                Ok(options)
+            });
+            match packed {
+                Ok(options) => return Ok((options, block)),
+                Err(err) => error = Some(err),
+            }
+        }
+        cart_size *= 2;
+    }
+    Err(format!(
+        "Unable to fit a batteryless save of {} bytes in a {:?} cart of up to {} banks{}",
+        save_size,
+        mapper,
+        mapper.max_rom_banks(),
+        error.map_or(String::new(), |err| format!(": {}", err))
+    ))
+}
+
+/// Check no packed area or previously linked data is inside the save block
+pub fn check_save_block(block: &SaveBlock, result: &PackResult, options: &PackOptions) -> Result<(), String> {
+    if let Some(placement) = result.placements.iter().find(|placement| block.contains(placement.bank)) {
+        return Err(format!(
+            "Area of {} bytes from bank {} was packed into bank {} which is inside the batteryless save block (banks {}-{})",
+            placement.size, placement.from, placement.bank, block.first_bank, block.last_bank
+        ));
+    }
+    if let Some(bank) = (block.first_bank..=block.last_bank).find(|&bank| options.existing(bank) > 0) {
+        return Err(format!(
+            "Bank {} has {} bytes used by the link map but is inside the batteryless save block (banks {}-{})",
+            bank,
+            options.existing(bank),
+            block.first_bank,
+            block.last_bank
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use crate::linkmap::{area_bank, MapArea};
+use crate::{ObjectData, BANK_SIZE};
+
+/// Planned and linked usage of a single bank
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BankCheck {
+    pub bank: u32,
+    /// Bytes placed in the bank by the packed object files
+    pub planned: u32,
+    /// Bytes the linker reported for the bank's `_CODE_N` area
+    pub actual: u32,
+}
+
+impl BankCheck {
+    /// Difference between the linked and planned size, positive when the
+    /// linker added padding or library code that gbspack didn't know about
+    pub fn drift(&self) -> i64 {
+        self.actual as i64 - self.planned as i64
+    }
+
+    pub fn is_overflow(&self) -> bool {
+        self.actual > BANK_SIZE
+    }
+}
+
+/// Compare the banks used by packed object files against the
+/// `_CODE_N` areas from the map file of the final link
+pub fn verify_banks(objects: &[ObjectData], areas: &[MapArea]) -> Vec<BankCheck> {
+    let mut checks: BTreeMap<u32, BankCheck> = BTreeMap::new();
+    for area in objects.iter().flat_map(|object| object.banks.iter()) {
+        checks
+            .entry(area.bank)
+            .or_insert(BankCheck { bank: area.bank, planned: 0, actual: 0 })
+            .planned += area.size;
+    }
+    for area in areas {
+        if let Some(bank) = area_bank(&area.name) {
+            checks
+                .entry(bank)
+                .or_insert(BankCheck { bank, planned: 0, actual: 0 })
+                .actual += area.size;
+        }
+    }
+    checks.into_iter().map(|(_, check)| check).collect()
+}
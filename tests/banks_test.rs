@@ -0,0 +1,67 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::banks::BankSet;
+
+  #[test]
+  fn test_parse_bank_set() {
+    let set: BankSet = "254,255".parse().unwrap();
+    assert_eq!(set.banks(), vec![254, 255]);
+    let set: BankSet = " 1-4, 8 ,3".parse().unwrap();
+    assert_eq!(set.banks(), vec![1, 2, 3, 4, 8]);
+    assert!(set.contains(2));
+    assert!(!set.contains(5));
+    assert!("".parse::<BankSet>().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_parse_bank_set_errors() {
+    assert!("8-4".parse::<BankSet>().is_err());
+    assert!("1,x".parse::<BankSet>().is_err());
+    assert!("600".parse::<BankSet>().is_err());
+  }
+
+  #[test]
+  fn test_bank_ranges_match_reserve_rules() {
+    // Bank lists and reserve rules share one range parser and its errors
+    let reserve = |spec: &str| spec.parse::<gbspacklib::reserve::ReserveRule>().unwrap_err();
+    let list = |spec: &str| spec.parse::<BankSet>().unwrap_err();
+    assert!(list("8-4").ends_with("bank range 8-4 is reversed"));
+    assert!(reserve("8-4:10").ends_with("bank range 8-4 is reversed"));
+    assert!(list("600").ends_with("bank 600 is out of range (0-511)"));
+    assert!(reserve("600:10").ends_with("bank 600 is out of range (1-511)"));
+    assert_eq!("0".parse::<BankSet>().unwrap().banks(), vec![0]);
+    assert!(reserve("0:10").ends_with("bank 0 is out of range (1-511)"));
+  }
+
+  #[test]
+  fn test_pack_multiple_filters() {
+    let area = gbspacklib::ObjectBankData::new;
+    let input = vec![
+      gbspacklib::ObjectData {
+        filename: "scene.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![area(9000, 255)],
+      },
+      gbspacklib::ObjectData {
+        filename: "plugin.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![area(9000, 254)],
+      },
+      gbspacklib::ObjectData {
+        filename: "engine.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![area(4000, 2)],
+      },
+    ];
+    let filter: BankSet = "254-255".parse().unwrap();
+    let options = gbspacklib::options::PackOptions::builder()
+      .filters(&filter.banks())
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 2, 2]);
+  }
+}
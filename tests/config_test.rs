@@ -0,0 +1,135 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::config::parse_config;
+
+  #[test]
+  fn test_parse_config() {
+    let input = r#"
+bank = 6
+filter = 255
+mapper = "mbc1"
+reserve = ["1:7F3", "2:00F"]
+output = "build/packed"
+ext = "rel"
+groups = [["scene1.o", "scene1_tiles.o"]]
+
+[pins]
+"music.o" = 8
+
+[report]
+file = "build/packed.lst"
+print_cart = true
+"#;
+    let config = parse_config(input).unwrap();
+    assert_eq!(config.bank, Some(6));
+    assert_eq!(config.filter.unwrap().banks(), vec![255]);
+    assert_eq!(config.mapper, Some(gbspacklib::Mapper::Mbc1));
+    assert_eq!(config.reserve, vec!["1:7F3", "2:00F"]);
+    assert_eq!(config.output, Some("build/packed".to_string()));
+    assert_eq!(config.ext, Some("rel".to_string()));
+    assert_eq!(config.groups, vec![vec!["scene1.o", "scene1_tiles.o"]]);
+    assert_eq!(config.pins.get("music.o"), Some(&8));
+    assert_eq!(config.report.file, Some("build/packed.lst".to_string()));
+    assert!(config.report.print_cart);
+    assert!(!config.report.print_max);
+    assert_eq!(config.additional, None);
+  }
+
+  #[test]
+  fn test_parse_config_filter() {
+    assert_eq!(parse_config("filter = [254, 255]").unwrap().filter.unwrap().banks(), vec![254, 255]);
+    assert_eq!(parse_config("filter = \"253-255\"").unwrap().filter.unwrap().banks(), vec![253, 254, 255]);
+    assert!(parse_config("filter = \"255-250\"").is_err());
+  }
+
+  #[test]
+  fn test_parse_config_pools() {
+    let input = r#"
+[[pools]]
+name = "music"
+banks = "8-15"
+objects = ["music_*.o"]
+fallback = "default"
+
+[[pools]]
+name = "graphics"
+banks = [16, 17]
+inputs = ["build/gfx"]
+"#;
+    let config = parse_config(input).unwrap();
+    assert_eq!(config.pools.len(), 2);
+    assert_eq!(config.pools[0].banks.banks(), (8..=15).collect::<Vec<u32>>());
+    assert_eq!(config.pools[0].fallback, Some("default".to_string()));
+    assert_eq!(config.pools[1].inputs, vec!["build/gfx"]);
+
+    let pool: gbspacklib::config::PoolConfig = "music:8-15:music_*.o,song.o:default".parse().unwrap();
+    assert_eq!(pool.name, "music");
+    assert_eq!(pool.objects, vec!["music_*.o", "song.o"]);
+    assert_eq!(pool.fallback, Some("default".to_string()));
+    assert!("music:8-15".parse::<gbspacklib::config::PoolConfig>().is_err());
+    assert!("music:x:a.o".parse::<gbspacklib::config::PoolConfig>().is_err());
+  }
+
+  #[test]
+  fn test_parse_config_errors() {
+    assert!(parse_config("bank = \"one\"").is_err());
+    assert!(parse_config("mapper = \"mbc9\"").is_err());
+    assert!(parse_config("unknown_option = 1").is_err());
+  }
+
+  #[test]
+  fn test_resolve_config_paths() {
+    let input = r#"
+inputs = ["obj", "/abs/obj", "@objs.rsp"]
+output = "build/packed"
+link_map = "build/game.map"
+lock = "gbspack.lock"
+pins = { "obj/music.o" = 8 }
+
+[[pools]]
+name = "gfx"
+banks = "16-17"
+inputs = ["build/gfx"]
+
+[report]
+file = "build/packed.lst"
+
+[linker]
+file = "build/cart.lkopts"
+"#;
+    let mut config = parse_config(input).unwrap();
+    config.resolve_paths(std::path::Path::new("project"));
+    assert_eq!(config.inputs, vec!["project/obj", "/abs/obj", "@project/objs.rsp"]);
+    assert_eq!(config.output, Some("project/build/packed".to_string()));
+    assert_eq!(config.link_map, Some("project/build/game.map".to_string()));
+    assert_eq!(config.lock, Some("project/gbspack.lock".to_string()));
+    assert_eq!(config.pools[0].inputs, vec!["project/build/gfx"]);
+    assert_eq!(config.report.file, Some("project/build/packed.lst".to_string()));
+    assert_eq!(config.linker.file, Some("project/build/cart.lkopts".to_string()));
+    assert_eq!(config.header, None);
+    // Object names are matched against inputs, not opened
+    assert_eq!(config.pins.get("obj/music.o"), Some(&8));
+  }
+
+  #[test]
+  fn test_load_config_relative_paths() {
+    let dir = std::env::temp_dir().join("gbspack_config_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let filename = dir.join("gbspack.toml");
+    std::fs::write(&filename, "output = \"build\"\n").unwrap();
+    let config = gbspacklib::config::load_config(filename.to_str()).unwrap();
+    assert_eq!(config.output, Some(dir.join("build").to_str().unwrap().to_string()));
+  }
+
+  #[test]
+  fn test_matches_object() {
+    assert!(gbspacklib::config::matches_object("scene1.o", "build/obj/scene1.o"));
+    assert!(gbspacklib::config::matches_object("build/obj/scene1.o", "build/obj/scene1.o"));
+    assert!(!gbspacklib::config::matches_object("scene1.o", "build/obj/scene10.o"));
+    assert!(gbspacklib::config::matches_pattern("music_*.o", "build/obj/music_1.o"));
+    assert!(gbspacklib::config::matches_pattern("build/*/scene1.o", "build/obj/scene1.o"));
+    assert!(!gbspacklib::config::matches_pattern("music_*.o", "build/obj/scene1.o"));
+  }
+}
@@ -0,0 +1,118 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::emit::BankSymbol;
+
+  fn packed() -> Vec<gbspacklib::ObjectPatch> {
+    vec![
+      gbspacklib::ObjectPatch {
+        filename: "scene1.o".to_string(),
+        contents: "XL3
+H 2 areas 5 global symbols
+S ___bank_scene1 Def0000FF
+S ___bank_tiles Ref000000
+S ___bank_engine Def000002
+A _CODE size 0 flags 0 addr 0
+A _CODE_255 size 55 flags 0 addr 0"
+          .to_string(),
+        replacements: vec![
+          gbspacklib::BankReplacement { from: 2, to: 2 },
+          gbspacklib::BankReplacement { from: 255, to: 7 },
+        ],
+      },
+      gbspacklib::ObjectPatch {
+        filename: "music.o".to_string(),
+        contents: "S ___bank_song_1 Def0000FF\nS ___bank_unpacked Def000003".to_string(),
+        replacements: vec![gbspacklib::BankReplacement { from: 255, to: 12 }],
+      },
+    ]
+  }
+
+  // The objects as read before packing, with no pins applied
+  fn inputs(packed: &[gbspacklib::ObjectPatch]) -> Vec<gbspacklib::ObjectData> {
+    packed
+      .iter()
+      .map(|patch| gbspacklib::ObjectData {
+        filename: patch.filename.clone(),
+        contents: patch.contents.clone(),
+        banks: vec![],
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_bank_symbols() {
+    assert_eq!(
+      gbspacklib::emit::bank_symbols(&packed(), &inputs(&packed())),
+      Ok(vec![
+        BankSymbol { name: "scene1".to_string(), bank: 7 },
+        BankSymbol { name: "song_1".to_string(), bank: 12 },
+      ])
+    );
+  }
+
+  #[test]
+  fn test_pinned_bank_symbols() {
+    // scene1.o was pinned from bank 255 to bank 8 before packing so kept its bank
+    let inputs = inputs(&packed());
+    let mut pinned = packed();
+    pinned[0].contents = pinned[0].contents.replace("Def0000FF", "Def000008");
+    pinned[0].replacements = vec![
+      gbspacklib::BankReplacement { from: 2, to: 2 },
+      gbspacklib::BankReplacement { from: 8, to: 8 },
+    ];
+    assert_eq!(
+      gbspacklib::emit::bank_symbols(&pinned, &inputs),
+      Ok(vec![
+        BankSymbol { name: "scene1".to_string(), bank: 8 },
+        BankSymbol { name: "song_1".to_string(), bank: 12 },
+      ])
+    );
+  }
+
+  #[test]
+  fn test_bank_symbol_errors() {
+    let mut duplicate = packed();
+    duplicate[1].contents = "S ___bank_scene1 Def0000FF".to_string();
+    assert!(gbspacklib::emit::bank_symbols(&duplicate, &[]).is_err());
+    let mut invalid = packed();
+    invalid[1].contents = "S ___bank_song.1 Def0000FF".to_string();
+    assert!(gbspacklib::emit::bank_symbols(&invalid, &[]).is_err());
+  }
+
+  #[test]
+  fn test_c_header() {
+    let symbols = gbspacklib::emit::bank_symbols(&packed(), &inputs(&packed())).unwrap();
+    let header = gbspacklib::emit::to_c_header(&symbols, 12, 16, None);
+    assert!(header.contains("#define GBSPACK_MAX_BANK 12\n"));
+    assert!(header.contains("#define GBSPACK_CART_SIZE 16\n"));
+    assert!(header.contains("#define BANK_scene1 7\n"));
+    assert!(header.contains("#define BANK_song_1 12\n"));
+    assert!(!header.contains("BANK_unpacked"));
+    assert!(!header.contains("GBSPACK_SAVE"));
+    let save = gbspacklib::save::SaveBlock { first_bank: 12, last_bank: 15, cart_size: 16 };
+    let header = gbspacklib::emit::to_c_header(&symbols, 15, 16, Some(&save));
+    assert!(header.contains("#define GBSPACK_SAVE_FIRST_BANK 12\n"));
+    assert!(header.contains("#define GBSPACK_SAVE_LAST_BANK 15\n"));
+    assert!(header.contains("#define GBSPACK_SAVE_BANKS 4\n"));
+  }
+
+  #[test]
+  fn test_asm_include() {
+    let symbols = gbspacklib::emit::bank_symbols(&packed(), &inputs(&packed())).unwrap();
+    let asxxxx = gbspacklib::emit::to_asm_include(&symbols, gbspacklib::emit::AsmSyntax::Asxxxx, 12, 16, None);
+    assert!(asxxxx.contains("GBSPACK_CART_SIZE == 16\n"));
+    assert!(asxxxx.contains("BANK_scene1 == 7\n"));
+    assert!(asxxxx.contains("BANK_song_1 == 12\n"));
+    let save = gbspacklib::save::SaveBlock { first_bank: 12, last_bank: 15, cart_size: 16 };
+    let asxxxx = gbspacklib::emit::to_asm_include(&symbols, gbspacklib::emit::AsmSyntax::Asxxxx, 15, 16, Some(&save));
+    assert!(asxxxx.contains("GBSPACK_SAVE_FIRST_BANK == 12\n"));
+    let rgbds = gbspacklib::emit::to_asm_include(&symbols, gbspacklib::emit::AsmSyntax::Rgbds, 12, 16, None);
+    assert!(rgbds.contains("DEF GBSPACK_MAX_BANK EQU 12\n"));
+    assert!(rgbds.contains("DEF BANK_song_1 EQU 12\n"));
+    assert!(!rgbds.contains("BANK_engine"));
+    assert_eq!("RGBDS".parse::<gbspacklib::emit::AsmSyntax>(), Ok(gbspacklib::emit::AsmSyntax::Rgbds));
+    assert!("nasm".parse::<gbspacklib::emit::AsmSyntax>().is_err());
+  }
+}
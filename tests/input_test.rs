@@ -0,0 +1,141 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::input::InputArg;
+  use std::fs;
+
+  fn temp_path(name: &str) -> String {
+    let dir = std::env::temp_dir().join("gbspack_input_test");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name).to_str().unwrap().to_owned()
+  }
+
+  #[test]
+  fn test_split_args() {
+    assert_eq!(
+      gbspacklib::input::split_args("a.o  \"my dir/b.o\" 'c d.o' e\\f.o"),
+      vec!["a.o", "my dir/b.o", "c d.o", "e\\f.o"]
+    );
+    assert_eq!(
+      gbspacklib::input::split_args("\"quote \\\" inside.o\" ; comment a.o"),
+      vec!["quote \" inside.o"]
+    );
+    assert_eq!(gbspacklib::input::split_args("# only a comment"), Vec::<String>::new());
+    assert_eq!(
+      gbspacklib::input::split_args("\"C:\\obj\\a.o\" \"sub dir\\scene1.o\""),
+      vec!["C:\\obj\\a.o", "sub dir\\scene1.o"]
+    );
+  }
+
+  #[test]
+  fn test_parse_linker_file() {
+    let input = "-mjwxi
+-k /opt/gbdk/lib/small/asxxxx/gbz80/
+-l gbz80.lib
+; banked data
+-b _CODE=0x200
+-g .STACK=0xE000
+game.ihx
+obj/scene1.o obj/scene2.o
+
+\"obj/my scene.o\"
+-Wl-fmore.lk
+@extra.rsp
+-e
+obj/ignored.o";
+    assert_eq!(
+      gbspacklib::input::parse_input_list(input),
+      vec![
+        InputArg::Object("obj/scene1.o".to_string()),
+        InputArg::Object("obj/scene2.o".to_string()),
+        InputArg::Object("obj/my scene.o".to_string()),
+        InputArg::Include("more.lk".to_string()),
+        InputArg::Include("extra.rsp".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_read_nested_input_files() {
+    let inner = temp_path("inner.rsp");
+    let outer = temp_path("outer.lk");
+    fs::write(&inner, "c.o\n# comment\n").unwrap();
+    fs::write(&outer, format!("-o game.gb a.o\n@\"{}\"\nb.o\n", inner)).unwrap();
+    assert_eq!(gbspacklib::input::read_input_file(&outer).unwrap(), vec!["a.o", "c.o", "b.o"]);
+    assert_eq!(
+      gbspacklib::input::expand_input_args(&["x.o".to_string(), format!("@{}", inner)]).unwrap(),
+      vec!["x.o", "c.o"]
+    );
+  }
+
+  #[test]
+  fn test_recursive_input_file() {
+    let recursive = temp_path("recursive.rsp");
+    fs::write(&recursive, format!("a.o @{}\n", recursive)).unwrap();
+    assert!(gbspacklib::input::read_input_file(&recursive).is_err());
+  }
+
+  #[test]
+  fn test_resolve_directory_inputs() {
+    let dir = temp_path("resolve_dir");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(format!("{}/sub", dir)).unwrap();
+    for file in &["b.o", "a.o", "notes.txt", "sub/c.o", "sub/music_1.o"] {
+      fs::write(format!("{}/{}", dir, file), "").unwrap();
+    }
+    let resolved = gbspacklib::input::resolve_inputs(
+      &[dir.clone(), format!("{}/b.o", dir)],
+      &[],
+      &["music_*.o".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+      resolved,
+      vec![format!("{}/a.o", dir), format!("{}/b.o", dir), format!("{}/sub/c.o", dir)]
+    );
+  }
+
+  #[test]
+  fn test_resolve_glob_inputs() {
+    let dir = temp_path("resolve_glob");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for file in &["scene2.o", "scene1.o", "actor1.o"] {
+      fs::write(format!("{}/{}", dir, file), "").unwrap();
+    }
+    let resolved = gbspacklib::input::resolve_inputs(
+      &["first.o".to_string(), format!("{}/scene*.o", dir)],
+      &[],
+      &[],
+    )
+    .unwrap();
+    assert_eq!(
+      resolved,
+      vec!["first.o".to_string(), format!("{}/scene1.o", dir), format!("{}/scene2.o", dir)]
+    );
+    assert!(gbspacklib::input::resolve_inputs(&[format!("{}/missing*.o", dir)], &[], &[]).is_err());
+  }
+
+  #[test]
+  fn test_resolve_file_with_pattern_characters() {
+    let dir = temp_path("resolve_literal");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(format!("{}/scene[1].o", dir), "").unwrap();
+    let resolved = gbspacklib::input::resolve_inputs(&[format!("{}/scene[1].o", dir)], &[], &[]).unwrap();
+    assert_eq!(resolved, vec![format!("{}/scene[1].o", dir)]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_resolve_skips_symlinked_directories() {
+    let dir = temp_path("resolve_symlink");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(format!("{}/sub", dir)).unwrap();
+    fs::write(format!("{}/sub/a.o", dir), "").unwrap();
+    std::os::unix::fs::symlink(&dir, format!("{}/sub/loop", dir)).unwrap();
+    let resolved = gbspacklib::input::resolve_inputs(std::slice::from_ref(&dir), &[], &[]).unwrap();
+    assert_eq!(resolved, vec![format!("{}/sub/a.o", dir)]);
+  }
+}
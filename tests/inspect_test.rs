@@ -0,0 +1,76 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::ObjectBankData;
+
+  fn object(filename: &str, banks: Vec<ObjectBankData>) -> gbspacklib::ObjectData {
+    gbspacklib::ObjectData {
+      filename: filename.to_string(),
+      contents: "".to_string(),
+      banks,
+    }
+  }
+
+  #[test]
+  fn test_bank_usage() {
+    let objects = vec![
+      object("a.o", vec![ObjectBankData { size: 100, bank: 2, ..Default::default() }]),
+      object(
+        "b.o",
+        vec![
+          ObjectBankData { size: 200, bank: 1, ..Default::default() },
+          ObjectBankData { size: 50, bank: 2, ..Default::default() },
+        ],
+      ),
+    ];
+    let usage = gbspacklib::inspect::bank_usage(&objects);
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[0].bank, 1);
+    assert_eq!(usage[1].areas, vec![("a.o".to_string(), 100), ("b.o".to_string(), 50)]);
+    assert_eq!(usage[1].size(), 150);
+    assert_eq!(usage[1].free(), 16234);
+  }
+
+  #[test]
+  fn test_parse_area() {
+    assert_eq!(
+      gbspacklib::inspect::parse_area("A _CODE_255 size 55 flags 8 addr 10"),
+      Some(gbspacklib::inspect::AreaInfo {
+        name: "_CODE_255".to_string(),
+        bank: Some(255),
+        size: 0x55,
+        flags: 8,
+        addr: 0x10,
+      })
+    );
+    assert_eq!(gbspacklib::inspect::parse_area("A _DATA size 4 flags 0 addr 0").unwrap().bank, None);
+    assert_eq!(gbspacklib::inspect::parse_area("S _main Def000000"), None);
+  }
+
+  #[test]
+  fn test_object_info() {
+    let contents = "XL3
+H 2 areas 5 global symbols
+S b_wait_frames Def0000FF
+S _wait_frames Def000000
+S ___bank_SCRIPT_3 Def0000FF
+S ___bank_tiles Ref000000
+A _CODE size 0 flags 0 addr 0
+A _CODE_255 size 55 flags 0 addr 0";
+    let object = gbspacklib::ObjectData {
+      filename: "script.o".to_string(),
+      contents: contents.to_string(),
+      banks: gbspacklib::parse_sizes(contents).unwrap(),
+    };
+    let info = gbspacklib::inspect::object_info(&object);
+    assert_eq!(info.version, "XL3");
+    assert_eq!(info.header, "H 2 areas 5 global symbols");
+    assert_eq!(info.areas.len(), 2);
+    assert_eq!(info.symbols.len(), 4);
+    assert_eq!(
+      info.rewrites,
+      vec![(255, vec!["b_wait_frames".to_string(), "___bank_SCRIPT_3".to_string()])]
+    );
+  }
+}
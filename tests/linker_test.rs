@@ -0,0 +1,62 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::linker::LinkerArgs;
+  use gbspacklib::Mapper;
+
+  #[test]
+  fn test_linker_args() {
+    let args = LinkerArgs { mapper: Mapper::Mbc5, rom_banks: 64, ram_banks: 4, battery: true };
+    assert_eq!(args.to_args().unwrap(), vec!["-Wl-yt0x1B", "-Wl-yo64", "-Wl-ya4"]);
+    let args = LinkerArgs { mapper: Mapper::Mbc1, rom_banks: 32, ram_banks: 0, battery: false };
+    assert_eq!(args.to_args().unwrap(), vec!["-Wl-yt0x01", "-Wl-yo32"]);
+    let args = LinkerArgs { mapper: Mapper::Mbc3, rom_banks: 8, ram_banks: 1, battery: false };
+    assert_eq!(args.cart_type(), Ok(0x12));
+  }
+
+  #[test]
+  fn test_linker_args_errors() {
+    let args = LinkerArgs { mapper: Mapper::Mbc5, rom_banks: 8, ram_banks: 0, battery: true };
+    assert!(args.to_args().is_err());
+    let args = LinkerArgs { mapper: Mapper::Mbc1, rom_banks: 256, ram_banks: 0, battery: false };
+    assert!(args.to_args().is_err());
+    let args = LinkerArgs { mapper: Mapper::Mbc3, rom_banks: 8, ram_banks: 8, battery: false };
+    assert!(args.to_args().is_err());
+  }
+
+  #[test]
+  fn test_base_address_args() {
+    let object = |filename: &str, areas: &[(u32, u32)]| gbspacklib::ObjectData {
+      filename: filename.to_string(),
+      contents: "".to_string(),
+      banks: areas
+        .iter()
+        .map(|&(size, flags)| gbspacklib::ObjectBankData { size, bank: 255, flags, addr: 0 })
+        .collect(),
+    };
+    let input = vec![
+      object("a.o", &[(10000, 0), (300, gbspacklib::AREA_PAG)]),
+      object("b.o", &[(200, 0)]),
+    ];
+    let options = gbspacklib::options::PackOptions::builder().bank_offset(2).build().unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let offsets: Vec<(u32, u32)> = result.placements.iter().map(|p| (p.bank, p.offset)).collect();
+    assert_eq!(offsets, vec![(2, 0), (2, 10240), (2, 10540)]);
+    assert_eq!(gbspacklib::linker::base_address_args(&result.banks), vec!["-Wl-b_CODE_2=0x24000"]);
+
+    // Existing bytes and areas kept in the bank come before the packed areas
+    let mut fixed = object("fixed.o", &[(500, 0)]);
+    fixed.banks[0].bank = 2;
+    let input = vec![object("a.o", &[(10000, 0)]), fixed];
+    let options = gbspacklib::options::PackOptions::builder()
+      .bank_offset(2)
+      .filter(255)
+      .existing(vec![0, 0, 1000])
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let offsets: Vec<(u32, u32)> = result.placements.iter().map(|p| (p.bank, p.offset)).collect();
+    assert_eq!(offsets, vec![(2, 1500), (2, 1000)]);
+  }
+}
@@ -0,0 +1,208 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::linkmap::MapArea;
+
+  const MAP: &str = "
+Area                                    Addr        Size        Decimal Bytes (Attributes)
+--------------------------------        ----        ----        ------- ----- ------------
+_CODE                               00000200    00001C4E =        7246. bytes (REL,CON)
+
+      Value  Global                              Global Defined In Module
+      -----  --------------------------------   ------------------------
+     00000200  _main                              main
+
+Area                                    Addr        Size        Decimal Bytes (Attributes)
+--------------------------------        ----        ----        ------- ----- ------------
+_CODE_1                             00014000    000012A3 =        4771. bytes (REL,CON)
+
+      Value  Global                              Global Defined In Module
+      -----  --------------------------------   ------------------------
+     00014000  _engine_update                     engine
+
+Area                                    Addr        Size        Decimal Bytes (Attributes)
+--------------------------------        ----        ----        ------- ----- ------------
+_CODE_2                             00024000    00000100 =         256. bytes (REL,CON)
+";
+
+  const NOI: &str = "DEF l__CODE 0x1C4E
+DEF s__CODE 0x200
+DEF l__CODE_1 0x12A3
+DEF s__CODE_1 0x14000
+DEF _main 0x200
+DEF l__CODE_2 0x100
+DEF s__CODE_2 0x24000
+";
+
+  #[test]
+  fn test_parse_map() {
+    let areas = gbspacklib::linkmap::parse_map(MAP);
+    assert_eq!(areas.len(), 3);
+    assert_eq!(
+      areas[1],
+      MapArea { name: "_CODE_1".to_string(), addr: 0x14000, size: 0x12A3 }
+    );
+  }
+
+  #[test]
+  fn test_parse_noi() {
+    assert_eq!(gbspacklib::linkmap::parse_noi(NOI), gbspacklib::linkmap::parse_map(MAP));
+  }
+
+  #[test]
+  fn test_base_addresses_match_map() {
+    let input = vec![gbspacklib::ObjectData {
+      filename: "a.o".to_string(),
+      contents: "".to_string(),
+      banks: vec![gbspacklib::ObjectBankData::new(256, 255)],
+    }];
+    let options = gbspacklib::options::PackOptions::builder().bank_offset(2).build().unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let code_2 = gbspacklib::linkmap::parse_map(MAP)
+      .into_iter()
+      .find(|area| area.name == "_CODE_2")
+      .unwrap();
+    assert_eq!(
+      gbspacklib::linker::base_address_args(&result.banks),
+      vec![format!("-Wl-b_CODE_2=0x{:X}", code_2.addr)]
+    );
+  }
+
+  #[test]
+  fn test_rom0_free() {
+    assert_eq!(gbspacklib::linkmap::rom0_free(&gbspacklib::linkmap::parse_map(MAP)), 0x4000 - 0x1E4E);
+    assert_eq!(gbspacklib::linkmap::rom0_free(&[]), 0x4000);
+  }
+
+  #[test]
+  fn test_bank_usage() {
+    let usage = gbspacklib::linkmap::bank_usage(&gbspacklib::linkmap::parse_map(MAP));
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[&1], 0x12A3);
+    assert_eq!(usage[&2], 0x100);
+  }
+
+  #[test]
+  fn test_pack_with_existing() {
+    let input = vec![
+      gbspacklib::ObjectData {
+        filename: "engine.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 4000, bank: 1, ..Default::default() }],
+      },
+      gbspacklib::ObjectData {
+        filename: "scene.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 8000, bank: 255, ..Default::default() }],
+      },
+    ];
+    // Bank 1 holds 10000 bytes including engine.o, bank 2 is full of library code
+    let areas = vec![
+      MapArea { name: "_CODE_1".to_string(), addr: 0x14000, size: 10000 },
+      MapArea { name: "_CODE_2".to_string(), addr: 0x24000, size: 15000 },
+    ];
+    let usage = gbspacklib::linkmap::outside_usage(&areas, &input, None);
+    assert_eq!(usage[&1], 6000);
+    assert_eq!(usage[&2], 15000);
+    let existing = vec![0, usage[&1], usage[&2]];
+    let options = gbspacklib::options::PackOptions::builder()
+      .filter(255)
+      .existing(existing)
+      .build()
+      .unwrap();
+    let output = gbspacklib::pack_objects(input, &options).unwrap().patches;
+    assert_eq!(output[1].replacements[0].to, 3);
+  }
+
+  #[test]
+  fn test_repack_with_own_link_map() {
+    let object = |filename: &str, size: u32| gbspacklib::ObjectData {
+      filename: filename.to_string(),
+      contents: format!("A _CODE_255 size {:X} flags 0 addr 0", size),
+      banks: vec![gbspacklib::ObjectBankData::new(size, 255)],
+    };
+    let input = vec![object("a.o", 9000), object("b.o", 7000), object("c.o", 5000)];
+    // Bank 1 starts with 1000 bytes of library code
+    let options = gbspacklib::options::PackOptions::builder()
+      .filter(255)
+      .existing(vec![0, 1000])
+      .build()
+      .unwrap();
+    let first = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let lock = gbspacklib::lock::PackLock::from_patches(&first.patches, first.max_bank).unwrap();
+
+    // Link the packed objects, giving a map which also holds the objects themselves
+    let areas: Vec<MapArea> = first
+      .banks
+      .iter()
+      .map(|bank| MapArea {
+        name: format!("_CODE_{}", bank.bank),
+        addr: (bank.bank << 16) | 0x4000,
+        size: bank.size(),
+      })
+      .collect();
+    assert_eq!(areas[0].size, 15000);
+    // Without the lock the objects' last banks are unknown so they count as used
+    assert_eq!(gbspacklib::linkmap::outside_usage(&areas, &input, None)[&1], 15000);
+    let usage = gbspacklib::linkmap::outside_usage(&areas, &input, Some(&lock));
+    assert_eq!(usage.values().copied().collect::<Vec<u32>>(), vec![1000, 0]);
+
+    let mut existing = vec![0; 3];
+    for (&bank, &size) in usage.iter() {
+      existing[bank as usize] = size;
+    }
+    let options = gbspacklib::options::PackOptions::builder()
+      .filter(255)
+      .existing(existing)
+      .build()
+      .unwrap();
+    let second = gbspacklib::pack_objects(input, &options).unwrap();
+    assert_eq!(second.placements, first.placements);
+    assert_eq!(second.max_bank, first.max_bank);
+  }
+
+  #[test]
+  fn test_rom0_free_with_own_link_map() {
+    let input = vec![gbspacklib::ObjectData {
+      filename: "font.o".to_string(),
+      contents: "A _CODE_255 size 190 flags 0 addr 0".to_string(),
+      banks: vec![gbspacklib::ObjectBankData::new(0x190, 255)],
+    }];
+    let options = gbspacklib::options::PackOptions::builder()
+      .unbanked(vec![0])
+      .rom0_free(0x400)
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    assert_eq!(result.placements[0].bank, 0);
+    let lock = gbspacklib::lock::PackLock::from_patches(&result.patches, result.max_bank).unwrap();
+
+    // font.o joined _CODE, leaving 0x400 - 0x190 bytes after it
+    let areas = vec![MapArea { name: "_CODE".to_string(), addr: 0x200, size: 0x3A00 + 0x190 }];
+    assert_eq!(gbspacklib::linkmap::rom0_free(&areas), 0x400 - 0x190);
+    assert_eq!(gbspacklib::linkmap::outside_rom0_free(&areas, &input, None), 0x400 - 0x190);
+    assert_eq!(gbspacklib::linkmap::outside_rom0_free(&areas, &input, Some(&lock)), 0x400);
+  }
+
+  #[test]
+  fn test_verify_banks() {
+    let packed = vec![gbspacklib::ObjectData {
+      filename: "scene.o".to_string(),
+      contents: "".to_string(),
+      banks: vec![
+        gbspacklib::ObjectBankData { size: 0x1200, bank: 1, ..Default::default() },
+        gbspacklib::ObjectBankData { size: 0x100, bank: 3, ..Default::default() },
+      ],
+    }];
+    let mut areas = gbspacklib::linkmap::parse_map(MAP);
+    areas.push(MapArea { name: "_CODE_4".to_string(), addr: 0x44000, size: 0x4010 });
+    let checks = gbspacklib::verify::verify_banks(&packed, &areas);
+    assert_eq!(checks.len(), 4);
+    assert_eq!((checks[0].bank, checks[0].planned, checks[0].actual), (1, 0x1200, 0x12A3));
+    assert_eq!(checks[0].drift(), 0xA3);
+    assert!(!checks[0].is_overflow());
+    assert_eq!(checks[2].drift(), -0x100);
+    assert!(checks[3].is_overflow());
+  }
+}
@@ -0,0 +1,75 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::lock::{AreaLock, ObjectLock, PackLock};
+
+  fn object(filename: &str, areas: Vec<(u32, u32)>) -> ObjectLock {
+    ObjectLock {
+      filename: filename.to_string(),
+      areas: areas
+        .into_iter()
+        .map(|(bank, size)| AreaLock { from: 255, bank, size })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_lock_from_patches() {
+    let packed = vec![gbspacklib::ObjectPatch {
+      filename: "a.o".to_string(),
+      contents: "A _CODE_1 size 10 flags 0 addr 0\nA _CODE_255 size 20 flags 0 addr 0".to_string(),
+      replacements: vec![
+        gbspacklib::BankReplacement { from: 1, to: 1 },
+        gbspacklib::BankReplacement { from: 255, to: 3 },
+      ],
+    }];
+    let lock = PackLock::from_patches(&packed, 3).unwrap();
+    assert_eq!(lock.cart_size, 4);
+    assert_eq!(
+      lock.objects[0].areas,
+      vec![AreaLock { from: 1, bank: 1, size: 16 }, AreaLock { from: 255, bank: 3, size: 32 }]
+    );
+    assert_eq!(PackLock::from_json(&lock.to_json()).unwrap(), lock);
+  }
+
+  #[test]
+  fn test_diff_locks() {
+    let old = PackLock {
+      max_bank: 2,
+      cart_size: 4,
+      objects: vec![
+        object("old/a.o", vec![(1, 100)]),
+        object("old/b.o", vec![(2, 100)]),
+        object("old/c.o", vec![(2, 100)]),
+      ],
+    };
+    let new = PackLock {
+      max_bank: 3,
+      cart_size: 4,
+      objects: vec![
+        object("new/a.o", vec![(1, 100)]),
+        object("new/b.o", vec![(2, 300)]),
+        object("new/c.o", vec![(1, 100)]),
+        object("new/d.o", vec![(3, 16000)]),
+      ],
+    };
+    let diff = gbspacklib::lock::diff_locks(&old, &new).unwrap();
+    assert_eq!(diff.areas.len(), 3);
+    assert_eq!(diff.areas[0].name, "b.o");
+    assert_eq!(diff.areas[0].growth(), 200);
+    assert!(!diff.areas[0].is_moved());
+    assert_eq!(diff.areas[1].name, "c.o");
+    assert!(diff.areas[1].is_moved());
+    assert!(diff.areas[2].is_added());
+    assert_eq!(diff.banks.len(), 3);
+    assert_eq!((diff.banks[0].bank, diff.banks[0].old_size, diff.banks[0].new_size), (1, 100, 200));
+    assert_eq!(diff.new_banks, vec![(3, vec!["d.o".to_string()])]);
+
+    // Objects in different directories with the same file name can't be matched
+    let mut duplicate = new.clone();
+    duplicate.objects.push(object("new/sub/a.o", vec![(1, 100)]));
+    assert!(gbspacklib::lock::diff_locks(&old, &duplicate).is_err());
+    assert!(gbspacklib::lock::diff_locks(&duplicate, &new).is_err());
+  }
+}
@@ -0,0 +1,377 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::options::{PackOptions, Pool, Strategy};
+  use gbspacklib::reserve::Reserve;
+  use gbspacklib::Mapper;
+
+  fn object(filename: &str, sizes: &[u32]) -> gbspacklib::ObjectData {
+    gbspacklib::ObjectData {
+      filename: filename.to_string(),
+      contents: "".to_string(),
+      banks: sizes
+        .iter()
+        .map(|&size| gbspacklib::ObjectBankData { size, bank: 255, ..Default::default() })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn test_default_options() {
+    let options = PackOptions::default();
+    assert_eq!(options.bank_offset(), 1);
+    assert!(options.filter().is_empty());
+    assert_eq!(options.mapper(), Mapper::Mbc5);
+    assert_eq!(options.strategy(), Strategy::FirstFit);
+    assert_eq!(options.bank_size(), 16384);
+    assert_eq!(options.max_bank(), 511);
+    assert!(options.is_autobanked(3));
+  }
+
+  #[test]
+  fn test_invalid_options() {
+    assert!(PackOptions::builder().bank_offset(0).build().is_err());
+    assert!(PackOptions::builder().mapper(Mapper::Mbc1).bank_offset(200).build().is_err());
+    assert!(PackOptions::builder().mapper(Mapper::Mbc3).max_bank(200).build().is_err());
+    assert!(PackOptions::builder().max_bank(8).bank_offset(9).build().is_err());
+    assert!(PackOptions::builder().bank_size(0).build().is_err());
+    assert!(PackOptions::builder().bank_size(0x8000).build().is_err());
+    assert!(PackOptions::builder().filter(0).build().is_err());
+    let mut reserve = vec![0; 4];
+    reserve[3] = 0x5000;
+    assert!(PackOptions::builder().reserve_sizes(&reserve).build().is_err());
+  }
+
+  #[test]
+  fn test_reserve_out_of_range() {
+    // Reserve lists shorter than the banks used no longer index out of bounds
+    let options = PackOptions::builder().reserve_sizes(&[0, 0x3000]).build().unwrap();
+    let result = gbspacklib::pack_objects(vec![object("a.o", &[0x2000, 0x2000, 0x2000])], &options).unwrap();
+    assert_eq!(result.max_bank, 3);
+    assert_eq!(options.reserved(1), 0x3000);
+    assert_eq!(options.reserved(1000), 0);
+
+    // Percentages are taken from the configured bank size
+    let reserve = gbspacklib::reserve::Reserve::parse(&["all:50%".to_string()]).unwrap();
+    let options = PackOptions::builder().bank_size(0x2000).reserve(reserve).build().unwrap();
+    assert_eq!(options.reserved(1), 0x1000);
+  }
+
+  #[test]
+  fn test_pack_errors() {
+    let options = PackOptions::builder().max_bank(2).build().unwrap();
+    assert!(gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000, 10000])], &options).is_err());
+    assert!(gbspacklib::pack_objects(vec![object("big.o", &[20000])], &PackOptions::default()).is_err());
+    let options = PackOptions::builder().groups(vec![vec![0, 5]]).build().unwrap();
+    assert!(gbspacklib::pack_objects(vec![object("a.o", &[100])], &options).is_err());
+    assert!(gbspacklib::pack_objects(vec![], &PackOptions::default()).is_ok());
+  }
+
+  #[test]
+  fn test_bank_size() {
+    let options = PackOptions::builder().bank_size(8192).build().unwrap();
+    let result = gbspacklib::pack_objects(vec![object("a.o", &[5000, 5000])], &options).unwrap();
+    assert_eq!(result.max_bank, 2);
+    assert_eq!(result.banks[0].free(), 8192 - 5000);
+  }
+
+  #[test]
+  fn test_best_fit() {
+    let input = vec![object("a.o", &[11384, 9384, 4000])];
+    let reserve = Reserve::parse(&["2:9C4".to_string()]).unwrap();
+    let options = PackOptions::builder().reserve(reserve.clone()).build().unwrap();
+    let first_fit = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = first_fit.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 2, 1]);
+
+    let options = PackOptions::builder()
+      .strategy(Strategy::BestFit)
+      .reserve(reserve)
+      .build()
+      .unwrap();
+    let best_fit = gbspacklib::pack_objects(input, &options).unwrap();
+    let placements: Vec<u32> = best_fit.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 2, 2]);
+  }
+
+  #[test]
+  fn test_allow_deny_banks() {
+    let options = PackOptions::builder()
+      .allow_banks("1-8".parse().unwrap())
+      .deny_banks("1-4,6".parse().unwrap())
+      .build()
+      .unwrap();
+    assert!(!options.is_allowed(4));
+    assert!(options.is_allowed(5));
+    assert!(!options.is_allowed(6));
+    assert!(!options.is_allowed(9));
+    let result = gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000, 10000])], &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![5, 7, 8]);
+    assert_eq!(result.patches[0].replacements[1].to, 7);
+    assert!(gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000, 10000, 10000])], &options).is_err());
+    assert!(PackOptions::builder().deny_banks("1-511".parse().unwrap()).build().is_err());
+
+    // Fixed engine code may sit in banks outside the allowed banks, but not in denied banks
+    let mut input = vec![object("engine.o", &[8000]), object("a.o", &[10000])];
+    input[0].banks[0].bank = 2;
+    let options = PackOptions::builder().filter(255).allow_banks("5-63".parse().unwrap()).build().unwrap();
+    assert!(!options.is_allowed(2));
+    assert!(!options.is_denied(2));
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![2, 5]);
+    assert_eq!(result.banks[1].size(), 8000);
+    let options = PackOptions::builder().filter(255).deny_banks("1-4".parse().unwrap()).build().unwrap();
+    assert!(gbspacklib::pack_objects(input, &options).is_err());
+    // MBC1 can't use bank 0x20 so allowing only that bank leaves nothing to pack into
+    let options = PackOptions::builder().mapper(Mapper::Mbc1).allow_banks("32".parse().unwrap()).build();
+    assert!(options.is_err());
+  }
+
+  #[test]
+  fn test_descending() {
+    // Without a top bank packing would start at the mapper's last bank
+    assert!(PackOptions::builder().descending(true).build().is_err());
+
+    let options = PackOptions::builder().max_bank(8).descending(true).build().unwrap();
+    assert!(options.descending());
+    let result = gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000, 4000])], &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![7, 8, 8]);
+    assert_eq!(result.max_bank, 8);
+    assert_eq!(result.banks[0].size(), 0);
+
+    let options = PackOptions::builder()
+      .bank_offset(6)
+      .max_bank(8)
+      .deny_banks("7".parse().unwrap())
+      .descending(true)
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000])], &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![6, 8]);
+    assert!(gbspacklib::pack_objects(vec![object("a.o", &[10000, 10000, 10000])], &options).is_err());
+  }
+
+  #[test]
+  fn test_spanning_areas() {
+    let input = vec![object("pcm.o", &[40000]), object("a.o", &[10000, 10000])];
+    assert!(gbspacklib::pack_objects(input.clone(), &PackOptions::default()).is_err());
+
+    let options = PackOptions::builder().spanning(vec![0]).build().unwrap();
+    assert!(options.is_spanning(0));
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 4, 5]);
+    assert_eq!(result.banks[1].spanned_from, Some(1));
+    assert_eq!(result.banks[2].spanned_from, Some(1));
+    assert_eq!(result.banks[3].spanned_from, None);
+    assert_eq!(result.patches[0].replacements[0].to, 1);
+
+    // Runs skip denied banks and banks already holding fixed areas
+    let mut fixed = input.clone();
+    fixed[1].banks[1].bank = 2;
+    let options = PackOptions::builder()
+      .filter(255)
+      .deny_banks("4".parse().unwrap())
+      .spanning(vec![0])
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(fixed, &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![5, 1, 2]);
+    assert_eq!(result.max_bank, 7);
+
+    let options = PackOptions::builder().max_bank(8).descending(true).spanning(vec![0]).build().unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    assert_eq!(result.placements[0].bank, 6);
+    assert_eq!(result.max_bank, 8);
+
+    let options = PackOptions::builder().max_bank(2).spanning(vec![0]).build().unwrap();
+    assert!(gbspacklib::pack_objects(input.clone(), &options).is_err());
+    let options = PackOptions::builder().spanning(vec![0]).groups(vec![vec![0, 1]]).build().unwrap();
+    assert!(gbspacklib::pack_objects(input, &options).is_err());
+  }
+
+  #[test]
+  fn test_sequences() {
+    let mut input = vec![
+      object("a.o", &[12000, 10000]),
+      object("song_1.o", &[10000]),
+      object("song_2.o", &[10000]),
+      object("song_3.o", &[10000]),
+    ];
+    input[0].banks[1].bank = 2;
+    let options = PackOptions::builder().filter(255).sequence(vec![3, 1, 2]).build().unwrap();
+    assert_eq!(options.sequences(), &[vec![3, 1, 2]]);
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 2, 4, 5, 3]);
+
+    let options = PackOptions::builder()
+      .filter(255)
+      .max_bank(8)
+      .descending(true)
+      .sequence(vec![1, 2, 3])
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![5, 2, 6, 7, 8]);
+
+    let options = PackOptions::builder().max_bank(2).sequence(vec![1, 2, 3]).build().unwrap();
+    assert!(gbspacklib::pack_objects(input.clone(), &options).is_err());
+    let options = PackOptions::builder().sequence(vec![1, 2]).groups(vec![vec![0, 1]]).build().unwrap();
+    assert!(gbspacklib::pack_objects(input.clone(), &options).is_err());
+    let options = PackOptions::builder().sequence(vec![1, 2]).sequence(vec![2, 3]).build().unwrap();
+    assert!(gbspacklib::pack_objects(input, &options).is_err());
+  }
+
+  #[test]
+  fn test_absolute_and_aligned_areas() {
+    let mut input = vec![object("a.o", &[10000]), object("abs.o", &[100])];
+    input[1].banks[0].bank = 3;
+    input[1].banks[0].flags = gbspacklib::AREA_ABS;
+    let result = gbspacklib::pack_objects(input.clone(), &PackOptions::default()).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 3]);
+
+    // Absolute areas can't be split from other areas in the same bank
+    input[1].banks.push(gbspacklib::ObjectBankData { size: 100, bank: 3, ..Default::default() });
+    assert!(gbspacklib::pack_objects(input, &PackOptions::default()).is_err());
+
+    // Paged areas count the most padding needed to align them
+    let mut input = vec![object("a.o", &[8000, 8000])];
+    let result = gbspacklib::pack_objects(input.clone(), &PackOptions::default()).unwrap();
+    assert_eq!(result.max_bank, 1);
+    input[0].banks[0].flags = gbspacklib::AREA_PAG;
+    input[0].banks[1].flags = gbspacklib::AREA_PAG;
+    let result = gbspacklib::pack_objects(input, &PackOptions::default()).unwrap();
+    assert_eq!(result.max_bank, 2);
+    assert_eq!(result.banks[0].size(), 8000 + 0xFF);
+  }
+
+  #[test]
+  fn test_rom0_promotion() {
+    let mut input = vec![
+      object("a.o", &[10000]),
+      object("small_1.o", &[300]),
+      object("small_2.o", &[200]),
+      object("small_3.o", &[1000]),
+    ];
+    input[1].contents = "A _CODE_255 size 12C flags 0 addr 0\nS ___bank_small_1 Def0000FF".to_string();
+    let options = PackOptions::builder().unbanked(vec![1, 2, 3]).rom0_free(600).build().unwrap();
+    assert!(options.is_unbanked(1));
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 0, 0, 1]);
+    assert_eq!(result.banks[0].size(), 11000);
+    let offsets: Vec<u32> = result.placements[1..3].iter().map(|p| p.offset).collect();
+    assert_eq!(offsets, vec![0x4000 - 600, 0x4000 - 600 + 300]);
+    let patch = &result.patches[1];
+    assert_eq!(patch.replacements[0].to, 0);
+    let contents = gbspacklib::replace_all_banks(&patch.contents, patch.replacements.clone());
+    assert_eq!(contents, "A _CODE size 12C flags 0 addr 0\nS ___bank_small_1 Def000000");
+
+    // Nothing is moved without free space or unbanked objects
+    let options = PackOptions::builder().unbanked(vec![1, 2, 3]).build().unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    assert!(result.placements.iter().all(|p| p.bank > 0));
+    let options = PackOptions::builder().rom0_free(600).build().unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    assert!(result.placements.iter().all(|p| p.bank > 0));
+    assert!(PackOptions::builder().rom0_free(0x5000).build().is_err());
+
+    // Areas from one bank are moved together or not at all
+    let mut input = vec![object("a.o", &[300, 200]), object("b.o", &[400])];
+    input[0].banks[1].bank = 255;
+    let options = PackOptions::builder().unbanked(vec![0, 1]).rom0_free(450).build().unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 1, 0]);
+  }
+
+  #[test]
+  fn test_fixed_area_in_denied_bank() {
+    let mut input = vec![object("a.o", &[100])];
+    input[0].banks[0].bank = 3;
+    let options = PackOptions::builder()
+      .filter(255)
+      .deny_banks("3".parse().unwrap())
+      .build()
+      .unwrap();
+    assert!(gbspacklib::pack_objects(input.clone(), &options).is_err());
+    input[0].banks[0].bank = 0x20;
+    let options = PackOptions::builder().filter(255).mapper(Mapper::Mbc1).build().unwrap();
+    assert!(gbspacklib::pack_objects(input, &options).is_err());
+  }
+
+  fn pool(name: &str, banks: &str, objects: Vec<usize>, fallback: Option<&str>) -> Pool {
+    Pool {
+      name: name.to_string(),
+      banks: banks.parse().unwrap(),
+      objects,
+      fallback: fallback.map(|name| name.to_string()),
+    }
+  }
+
+  #[test]
+  fn test_pack_pools() {
+    let input = vec![
+      object("script.o", &[10000]),
+      object("music.o", &[10000, 10000]),
+      object("tiles.o", &[6000]),
+      object("script2.o", &[10000]),
+    ];
+    let options = PackOptions::builder()
+      .pool(pool("music", "8-15", vec![1], None))
+      .pool(pool("graphics", "16-63", vec![2], None))
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 8, 9, 16, 2]);
+    assert_eq!(options.pool_name(options.pool_of(3)), "default");
+  }
+
+  #[test]
+  fn test_pool_overflow() {
+    let input = vec![object("music.o", &[10000, 10000, 10000])];
+    let options = PackOptions::builder()
+      .pool(pool("music", "8-9", vec![0], None))
+      .build()
+      .unwrap();
+    let err = gbspacklib::pack_objects(input.clone(), &options).unwrap_err();
+    assert!(err.contains("Pool \"music\" is full"));
+
+    let options = PackOptions::builder()
+      .pool(pool("music", "8-9", vec![0], Some("overflow")))
+      .pool(pool("overflow", "20-30", vec![], None))
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![8, 9, 20]);
+
+    let options = PackOptions::builder()
+      .pool(pool("music", "8-9", vec![0], Some("default")))
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![1, 8, 9]);
+  }
+
+  #[test]
+  fn test_invalid_pools() {
+    let build = |pools: Vec<Pool>| pools.into_iter().fold(PackOptions::builder(), |b, p| b.pool(p)).build();
+    assert!(build(vec![pool("a", "1-2", vec![], None), pool("a", "3-4", vec![], None)]).is_err());
+    assert!(build(vec![pool("default", "1-2", vec![], None)]).is_err());
+    assert!(build(vec![pool("a", "1-2", vec![], Some("missing"))]).is_err());
+    assert!(build(vec![pool("a", "1-2", vec![], Some("b")), pool("b", "3-4", vec![], Some("a"))]).is_err());
+    assert!(build(vec![pool("a", "1-2", vec![], Some("b")), pool("b", "3-4", vec![], Some("default"))]).is_ok());
+  }
+}
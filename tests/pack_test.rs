@@ -8,8 +8,8 @@ mod tests {
   #[test]
   fn test_parse_area_size() {
     let input = "A _CODE_3 size 8 flags 0 addr 0".to_owned();
-    let expected_output = gbspacklib::ObjectBankData { size: 8, bank: 3 };
-    assert_eq!(gbspacklib::parse_size(&input), expected_output);
+    let expected_output = gbspacklib::ObjectBankData { size: 8, bank: 3, ..Default::default() };
+    assert_eq!(gbspacklib::parse_size(&input), Ok(expected_output));
   }
 
   #[test]
@@ -18,8 +18,26 @@ mod tests {
     let expected_output = gbspacklib::ObjectBankData {
       size: 255,
       bank: 15,
+      ..Default::default()
     };
-    assert_eq!(gbspacklib::parse_size(&input), expected_output);
+    assert_eq!(gbspacklib::parse_size(&input), Ok(expected_output));
+  }
+
+  #[test]
+  fn test_parse_area_flags() {
+    let area = gbspacklib::parse_size("A _CODE_255 size 20 flags 18 addr 0").unwrap();
+    assert_eq!(area.flags, 0x18);
+    assert!(area.is_absolute());
+    assert_eq!(area.alignment(), 0x100);
+    assert_eq!(area.footprint(), 0x20 + 0xFF);
+    let area = gbspacklib::parse_size("A _CODE_2 size 10 flags 0 addr 4000").unwrap();
+    assert_eq!(area.addr, 0x4000);
+    assert!(area.is_absolute());
+    assert_eq!(area.footprint(), 0x10);
+    assert!(!gbspacklib::parse_size("A _CODE_2 size 10 flags 0 addr 0").unwrap().is_absolute());
+    assert!(gbspacklib::parse_size("A _CODE_X size 10 flags 0 addr 0").is_err());
+    assert!(gbspacklib::parse_size("A _CODE_2 size").is_err());
+    assert!(gbspacklib::parse_sizes("XL3\nA _CODE_2 size zz flags 0 addr 0").is_err());
   }
 
   #[test]
@@ -36,17 +54,38 @@ A _CODE_255 size 55 flags 0 addr 0
 S _SCRIPT_3 Def000000"
       .to_owned();
     let expected_output = vec![
-      gbspacklib::ObjectBankData { size: 5, bank: 5 },
+      gbspacklib::ObjectBankData { size: 5, bank: 5, ..Default::default() },
       gbspacklib::ObjectBankData {
         size: 85,
         bank: 255,
+        ..Default::default()
       },
     ];
-    let output = gbspacklib::parse_sizes(&input);
+    let output = gbspacklib::parse_sizes(&input).unwrap();
     assert_eq!(output.len(), 2);
     assert_eq!(output, expected_output);
   }
 
+  #[test]
+  fn test_parse_symbols() {
+    let input = "XL3
+H 2 areas 5 global symbols
+S b_wait_frames Ref000000
+S ___bank_SCRIPT_3 Def0000FF
+A _CODE_255 size 55 flags 0 addr 0
+S _SCRIPT_3 Def000012";
+    let output = gbspacklib::parse_symbols(input);
+    assert_eq!(output.len(), 3);
+    assert_eq!(
+      output[0],
+      gbspacklib::Symbol { name: "b_wait_frames".to_string(), def: false, value: 0 }
+    );
+    assert_eq!(
+      output[2],
+      gbspacklib::Symbol { name: "_SCRIPT_3".to_string(), def: true, value: 0x12 }
+    );
+  }
+
   #[test]
   fn test_pack_areas() {
     let input = vec![
@@ -54,10 +93,11 @@ S _SCRIPT_3 Def000000"
         filename: "a.o".to_string(),
         contents: "hello world".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 5, bank: 1 },
+          gbspacklib::ObjectBankData { size: 5, bank: 1, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
@@ -65,19 +105,21 @@ S _SCRIPT_3 Def000000"
         filename: "b.o".to_string(),
         contents: "second file".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 15, bank: 2 },
+          gbspacklib::ObjectBankData { size: 15, bank: 2, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 500,
             bank: 255,
+            ..Default::default()
           },
           gbspacklib::ObjectBankData {
             size: 40,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
     ];
-    let output = gbspacklib::pack_object_data(input, 255, 0, true);
+    let output = gbspacklib::pack_object_data(input, 255, 0, true, vec![0; 2048]);
     assert_eq!(output[0].filename, "a.o");
     assert_eq!(output[1].filename, "b.o");
     assert_eq!(output[0].replacements[0].from, 1);
@@ -100,10 +142,11 @@ S _SCRIPT_3 Def000000"
         filename: "a.o".to_string(),
         contents: "hello world".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 5, bank: 1 },
+          gbspacklib::ObjectBankData { size: 5, bank: 1, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
@@ -111,19 +154,21 @@ S _SCRIPT_3 Def000000"
         filename: "b.o".to_string(),
         contents: "second file".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 15, bank: 2 },
+          gbspacklib::ObjectBankData { size: 15, bank: 2, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
     ];
-    let output = gbspacklib::pack_object_data(input, 255, 31, true);
+    let output = gbspacklib::pack_object_data(input, 255, 31, true, vec![0; 2048]);
     assert_eq!(output[0].filename, "a.o");
     assert_eq!(output[1].filename, "b.o");
     assert_eq!(output[0].replacements[0].from, 1);
@@ -145,10 +190,11 @@ S _SCRIPT_3 Def000000"
         filename: "a.o".to_string(),
         contents: "hello world".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 5, bank: 1 },
+          gbspacklib::ObjectBankData { size: 5, bank: 1, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
@@ -156,22 +202,149 @@ S _SCRIPT_3 Def000000"
         filename: "b.o".to_string(),
         contents: "second file".to_string(),
         banks: vec![
-          gbspacklib::ObjectBankData { size: 15, bank: 2 },
+          gbspacklib::ObjectBankData { size: 15, bank: 2, ..Default::default() },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
           gbspacklib::ObjectBankData {
             size: 16380,
             bank: 255,
+            ..Default::default()
           },
         ],
       },
     ];
-    let output = gbspacklib::pack_object_data(input, 255, 35, true);
+    let output = gbspacklib::pack_object_data(input, 255, 35, true, vec![0; 2048]);
     assert_eq!(gbspacklib::get_patch_max_bank(&output), 37);
   }
 
+  #[test]
+  fn test_pack_groups() {
+    let input = vec![
+      gbspacklib::ObjectData {
+        filename: "a.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 10000, bank: 255, ..Default::default() }],
+      },
+      gbspacklib::ObjectData {
+        filename: "b.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 6000, bank: 255, ..Default::default() }],
+      },
+      gbspacklib::ObjectData {
+        filename: "c.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 7000, bank: 255, ..Default::default() }],
+      },
+    ];
+    let options = gbspacklib::options::PackOptions::builder()
+      .filter(255)
+      .groups(vec![vec![1, 2]])
+      .build()
+      .unwrap();
+    let output = gbspacklib::pack_objects(input, &options).unwrap().patches;
+    assert_eq!(output[0].replacements[0].to, 2);
+    assert_eq!(output[1].replacements[0].to, 1);
+    assert_eq!(output[2].replacements[0].to, 1);
+  }
+
+  #[test]
+  fn test_pack_result() {
+    let input = vec![
+      gbspacklib::ObjectData {
+        filename: "a.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![
+          gbspacklib::ObjectBankData { size: 10000, bank: 255, ..Default::default() },
+          gbspacklib::ObjectBankData { size: 10000, bank: 255, ..Default::default() },
+        ],
+      },
+      gbspacklib::ObjectData {
+        filename: "b.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 6000, bank: 3, ..Default::default() }],
+      },
+    ];
+    let mut reserve = vec![0; 2048];
+    reserve[2] = 100;
+    let options = gbspacklib::options::PackOptions::builder()
+      .bank_offset(2)
+      .filter(255)
+      .reserve_sizes(&reserve)
+      .build()
+      .unwrap();
+    let result = gbspacklib::pack_objects(input, &options).unwrap();
+    assert_eq!(result.max_bank, 3);
+    assert_eq!(result.cart_size, 4);
+    let placements: Vec<(usize, usize, u32)> = result.placements.iter().map(|p| (p.object, p.area, p.bank)).collect();
+    assert_eq!(placements, vec![(0, 0, 2), (0, 1, 3), (1, 0, 3)]);
+    assert_eq!(result.banks.len(), 3);
+    assert!(result.banks[0].areas.is_empty());
+    assert_eq!(result.banks[1].free(), 16384 - 10000 - 100);
+    assert_eq!(result.banks[2].size(), 16000);
+    assert_eq!(result.object_placements(0).count(), 2);
+    assert_eq!(result.patches[1].replacements[0].to, 3);
+  }
+
+  #[test]
+  fn test_explain_object() {
+    use gbspacklib::explain::AttemptOutcome;
+    let input = vec![
+      gbspacklib::ObjectData {
+        filename: "a.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![gbspacklib::ObjectBankData { size: 12000, bank: 255, ..Default::default() }],
+      },
+      gbspacklib::ObjectData {
+        filename: "b.o".to_string(),
+        contents: "".to_string(),
+        banks: vec![
+          gbspacklib::ObjectBankData { size: 6000, bank: 255, ..Default::default() },
+          gbspacklib::ObjectBankData { size: 100, bank: 3, ..Default::default() },
+        ],
+      },
+    ];
+    let mut reserve = vec![0; 2048];
+    reserve[3] = 12000;
+    let options = gbspacklib::options::PackOptions::builder()
+      .bank_offset(2)
+      .filter(255)
+      .reserve_sizes(&reserve)
+      .build()
+      .unwrap();
+    let traces = gbspacklib::explain_object(input, &options, 1).unwrap();
+    assert_eq!(traces.len(), 2);
+    assert!(traces[0].is_fixed());
+    assert_eq!(traces[0].bank, 3);
+    assert_eq!(traces[1].position, Some(2));
+    let outcomes: Vec<(u32, AttemptOutcome)> = traces[1].attempts.iter().map(|a| (a.bank, a.outcome)).collect();
+    assert_eq!(
+      outcomes,
+      vec![
+        (1, AttemptOutcome::BelowOffset),
+        (2, AttemptOutcome::NoSpace),
+        (3, AttemptOutcome::Reserved),
+        (4, AttemptOutcome::NewBank),
+      ]
+    );
+    assert_eq!(traces[1].attempts[2].free(), 16384 - 12000 - 100);
+    assert_eq!(traces[1].bank, 4);
+  }
+
+  #[test]
+  fn test_pin_object() {
+    let input = gbspacklib::ObjectData {
+      filename: "a.o".to_string(),
+      contents: "S ___bank_a Def0000FF\nA _CODE_255 size 10 flags 0 addr 0".to_string(),
+      banks: vec![gbspacklib::ObjectBankData { size: 16, bank: 255, ..Default::default() }],
+    };
+    let output = gbspacklib::pin_object(&input, 255, 4);
+    assert_eq!(output.contents, "S ___bank_a Def000004\nA _CODE_4 size 10 flags 0 addr 0");
+    assert_eq!(output.banks, vec![gbspacklib::ObjectBankData { size: 16, bank: 4, ..Default::default() }]);
+  }
+
   #[test]
   fn test_replace_one_bank() {
     let input = "XL3
@@ -289,4 +462,13 @@ S _SCRIPT_3 Def000000"
 
   }
 
+  #[test]
+  fn test_output_filename_collisions() {
+    let inputs = vec!["in/a/x.o".to_string(), "in/b/x.o".to_string()];
+    assert!(gbspacklib::check_output_filenames(&inputs, "", "o").is_ok());
+    assert!(gbspacklib::check_output_filenames(&inputs, "out", "o").is_err());
+    let inputs = vec!["in/x.o".to_string(), "in/x.rel".to_string()];
+    assert!(gbspacklib::check_output_filenames(&inputs, "", "rel").is_err());
+  }
+
 }
@@ -0,0 +1,64 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::reserve::{Reserve, ReserveAmount, ReserveBanks, ReserveRule};
+
+  #[test]
+  fn test_parse_reserve_rules() {
+    assert_eq!(
+      "1:7F3".parse::<ReserveRule>(),
+      Ok(ReserveRule { banks: ReserveBanks::Range(1, 1), amount: ReserveAmount::Bytes(0x7F3) })
+    );
+    assert_eq!(
+      "1-8:200".parse::<ReserveRule>(),
+      Ok(ReserveRule { banks: ReserveBanks::Range(1, 8), amount: ReserveAmount::Bytes(0x200) })
+    );
+    assert_eq!(
+      "all:10%".parse::<ReserveRule>(),
+      Ok(ReserveRule { banks: ReserveBanks::All, amount: ReserveAmount::Percent(10) })
+    );
+    assert_eq!(
+      "5:full".parse::<ReserveRule>(),
+      Ok(ReserveRule { banks: ReserveBanks::Range(5, 5), amount: ReserveAmount::Full })
+    );
+  }
+
+  #[test]
+  fn test_invalid_reserve_rules() {
+    assert!("1".parse::<ReserveRule>().is_err());
+    assert!("0:100".parse::<ReserveRule>().is_err());
+    assert!("4000:100".parse::<ReserveRule>().is_err());
+    assert!("8-1:100".parse::<ReserveRule>().is_err());
+    assert!("1:XYZ".parse::<ReserveRule>().is_err());
+    assert!("1:4001".parse::<ReserveRule>().is_err());
+    assert!("1:150%".parse::<ReserveRule>().is_err());
+    assert!(Reserve::parse(&["1:7F3,bad".to_string()]).is_err());
+  }
+
+  #[test]
+  fn test_reserve_for_bank() {
+    let reserve = Reserve::parse(&["1:7F3,all:10%".to_string(), "4-5:full".to_string()]).unwrap();
+    assert_eq!(reserve.for_bank(1), 0x7F3);
+    assert_eq!(reserve.for_bank(2), 1638);
+    assert_eq!(reserve.for_bank(4), 16384);
+    assert_eq!(reserve.for_bank(6), 1638);
+    assert_eq!(reserve.for_bank_size(2, 0x2000), 819);
+    assert_eq!(reserve.for_bank_size(4, 0x2000), 0x2000);
+    let reserve_vec = reserve.to_vec();
+    assert_eq!(reserve_vec[0], 0);
+    assert_eq!(reserve_vec[5], 16384);
+  }
+
+  #[test]
+  fn test_pack_with_reserve() {
+    let input = vec![gbspacklib::ObjectData {
+      filename: "a.o".to_string(),
+      contents: "".to_string(),
+      banks: vec![gbspacklib::ObjectBankData { size: 100, bank: 255, ..Default::default() }],
+    }];
+    let reserve = Reserve::parse(&["1-2:full".to_string()]).unwrap();
+    let output = gbspacklib::pack_object_data(input, 255, 1, false, reserve.to_vec());
+    assert_eq!(output[0].replacements[0].to, 3);
+  }
+}
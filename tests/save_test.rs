@@ -0,0 +1,88 @@
+extern crate gbspacklib;
+
+#[cfg(test)]
+mod tests {
+  use gbspacklib::options::PackOptions;
+  use gbspacklib::save::{check_save_block, parse_byte_size, reserve_save_block, SaveBlock};
+  use gbspacklib::Mapper;
+
+  #[test]
+  fn test_parse_byte_size() {
+    assert_eq!(parse_byte_size("32768"), Ok(32768));
+    assert_eq!(parse_byte_size("0x8000"), Ok(32768));
+    assert_eq!(parse_byte_size("32K"), Ok(32768));
+    assert_eq!(parse_byte_size("64kb"), Ok(65536));
+    assert!(parse_byte_size("0").is_err());
+    assert!(parse_byte_size("32M").is_err());
+  }
+
+  #[test]
+  fn test_check_save_block() {
+    let object = gbspacklib::ObjectData {
+      filename: "a.o".to_string(),
+      contents: "".to_string(),
+      banks: vec![
+        gbspacklib::ObjectBankData { size: 0x1000, bank: 255, ..Default::default() },
+        gbspacklib::ObjectBankData { size: 0x1000, bank: 13, ..Default::default() },
+      ],
+    };
+    let block = SaveBlock { first_bank: 12, last_bank: 15, cart_size: 16 };
+    let options = PackOptions::builder().filter(255).build().unwrap();
+    let result = gbspacklib::pack_objects(vec![object.clone()], &options).unwrap();
+    assert!(check_save_block(&block, &result, &options).is_err());
+
+    let mut existing = vec![0; 15];
+    existing[14] = 0x100;
+    let options = PackOptions::builder().existing(existing).build().unwrap();
+    let result = gbspacklib::pack_objects(vec![object], &options).unwrap();
+    assert_eq!(result.max_bank, 1);
+    assert!(check_save_block(&block, &result, &options).is_err());
+    assert!(check_save_block(&SaveBlock { first_bank: 4, last_bank: 7, cart_size: 8 }, &result, &options).is_ok());
+  }
+
+  #[test]
+  fn test_reserve_save_block() {
+    let object = |filename: &str| gbspacklib::ObjectData {
+      filename: filename.to_string(),
+      contents: "".to_string(),
+      banks: vec![gbspacklib::ObjectBankData::new(10000, 255)],
+    };
+    let input = vec![object("a.o"), object("b.o"), object("c.o")];
+    let (options, block) =
+      reserve_save_block(&input, &PackOptions::builder(), 0x8000, 0x10000, 0, Mapper::Mbc5).unwrap();
+    assert_eq!(block, SaveBlock { first_bank: 4, last_bank: 7, cart_size: 8 });
+    assert_eq!(options.max_bank(), 7);
+    assert!(!options.is_allowed(4));
+
+    // Packing downwards starts below the save block
+    let builder = PackOptions::builder().descending(true);
+    let (options, _) = reserve_save_block(&input, &builder, 0x8000, 0x10000, 0, Mapper::Mbc5).unwrap();
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    let placements: Vec<u32> = result.placements.iter().map(|p| p.bank).collect();
+    assert_eq!(placements, vec![3, 2, 1]);
+
+    // A fourth bank of data moves the save block to the end of a larger cart
+    let mut input = input;
+    input.push(object("d.o"));
+    let (options, block) = reserve_save_block(&input, &builder, 0x8000, 0x10000, 0, Mapper::Mbc5).unwrap();
+    assert_eq!(block, SaveBlock { first_bank: 12, last_bank: 15, cart_size: 16 });
+    let result = gbspacklib::pack_objects(input.clone(), &options).unwrap();
+    assert_eq!(result.max_bank, 11);
+    assert!(check_save_block(&block, &result, &options).is_ok());
+    let builder = PackOptions::builder().max_bank(3);
+    assert!(reserve_save_block(&input, &builder, 0x8000, 0x10000, 0, Mapper::Mbc5).is_err());
+
+    // Sectors smaller than a bank still reserve whole banks
+    let input: Vec<gbspacklib::ObjectData> = (0..5).map(|i| object(&format!("{}.o", i))).collect();
+    let (_, block) = reserve_save_block(&input, &PackOptions::builder(), 0x2000, 0x1000, 0, Mapper::Mbc5).unwrap();
+    assert_eq!(block, SaveBlock { first_bank: 7, last_bank: 7, cart_size: 8 });
+    assert_eq!(block.banks(), 1);
+    assert!(reserve_save_block(&input, &PackOptions::builder(), 0x8000, 0x3000, 0, Mapper::Mbc5).is_err());
+
+    // MBC1 can't use banks 0x20, 0x40 and 0x60 so 120 banks of data fill its largest cart
+    let input: Vec<gbspacklib::ObjectData> = (0..121).map(|i| object(&format!("{}.o", i))).collect();
+    let builder = PackOptions::builder().mapper(Mapper::Mbc1);
+    assert!(reserve_save_block(&input[..120], &builder, 0x8000, 0x10000, 0, Mapper::Mbc1).is_ok());
+    assert!(reserve_save_block(&input, &builder, 0x8000, 0x10000, 0, Mapper::Mbc1).is_err());
+  }
+}